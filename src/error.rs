@@ -29,6 +29,8 @@ pub enum ZokErrorType {
     SyntaxError(String),
     TypeError(String),
     UnsupportedError,
+    /// The prover refused the inputs, e.g. because an assertion failed.
+    ProofError(String),
     Unreachable,
 }
 
@@ -41,6 +43,8 @@ pub struct LexicalError {
 #[derive(Debug, PartialEq)]
 pub enum LexicalErrorType {
     UnrecognizedToken(char),
+    UnterminatedString,
+    InvalidEscape(char),
     OtherError(String),
 }
 
@@ -91,6 +95,7 @@ impl fmt::Display for ZokErrorType {
         match self {
             ZokErrorType::InvalidToken => write!(f, "Got invalid token"),
             ZokErrorType::UnrecognizedToken(c) => write!(f, "Got unexpected token"),
+            ZokErrorType::ProofError(err) => write!(f, "Cannot prove: {}", err),
             _ => write!(f, "Got parser Error"),
         }
     }
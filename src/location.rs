@@ -1,9 +1,15 @@
 use std::fmt;
 
+/// Position of a character in the source.
+///
+/// `row` and `column` are 1-based and `offset` is the absolute byte offset
+/// from the start of the source. `Location::default()` (all zero) is used
+/// for errors that have no position.
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct Location {
     row: usize,
     column: usize,
+    offset: usize,
 }
 
 impl fmt::Display for Location {
@@ -13,8 +19,13 @@ impl fmt::Display for Location {
 }
 
 impl Location {
-    pub fn new(row: usize, column: usize) -> Self {
-        Location { row, column }
+    pub fn new(row: usize, column: usize, offset: usize) -> Self {
+        Location { row, column, offset }
+    }
+
+    /// Location of the first character of a source.
+    pub fn start() -> Self {
+        Location::new(1, 1, 0)
     }
 
     pub fn row(&self) -> usize {
@@ -25,17 +36,24 @@ impl Location {
         self.column
     }
 
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
     pub fn reset(&mut self) {
-        self.row = 0;
-        self.column = 0;
+        *self = Location::start();
     }
 
-    pub fn go_right(&mut self) {
+    /// Moves past `c`, which is assumed to be on the current line.
+    pub fn go_right(&mut self, c: char) {
         self.column += 1;
+        self.offset += c.len_utf8();
     }
 
+    /// Moves past a `'\n'` to the first column of the next line.
     pub fn new_line(&mut self) {
         self.row += 1;
         self.column = 1;
+        self.offset += 1;
     }
 }
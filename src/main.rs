@@ -2,16 +2,24 @@
 
 use lalrpop_util::lalrpop_mod;
 use crate::error::ZokError;
-use crate::traverser::{ProofTraverser, VCTraverser};
+use crate::traverser::visitor::LocationEraser;
+use crate::traverser::{FmtTraverser, ProofOptions, ProofTraverser, StatsTraverser, VCTraverser, VisitorMut};
+use hex::FromHex;
+use std::{env, fs, process};
 use crate::parser::ast;
 use crate::parser::lexer::make_tokenizer;
-use crate::zkboo::zkboo::ZkBoo;
+use crate::zkboo::zkboo::Engine;
 
 mod parser;
 mod zkboo;
 mod location;
 mod error;
 mod traverser;
+#[cfg(test)]
+mod test_support;
+
+// Rounds of the emitted verifier and its proofs, and the default of `stats`.
+const ROUNDS: usize = 137;
 
 lalrpop_mod!(
     #[allow(clippy::all)]
@@ -37,11 +45,149 @@ pub fn parse_zok(code: &str) -> Result<ast::Program, ZokError> {
     Ok(ast)
 }
 
+/// `fmt [--check] <file>...` rewrites each file in canonical form. With
+/// `--check` nothing is written and the exit code is 1 if any file would
+/// change. Without any file it prints the usage and exits with 2, so a
+/// check that names no files does not pass silently. A file whose program
+/// would not survive the formatting is left alone and also exits with 2.
+fn fmt(args: &[String]) -> i32 {
+    let check = args.iter().any(|arg| arg == "--check");
+    let paths: Vec<_> = args.iter().filter(|arg| *arg != "--check").collect();
+    if paths.is_empty() {
+        eprintln!("usage: fmt [--check] <file>...");
+        return 2;
+    }
+    let mut code = 0;
+    for path in paths {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("{}: {}", path, err);
+                return 2;
+            }
+        };
+        let ast = match parse_zok(&source) {
+            Ok(ast) => ast,
+            Err(err) => {
+                eprintln!("{}: {} at {}", path, err, err.location);
+                return 2;
+            }
+        };
+        let formatted = FmtTraverser::traverse(&ast);
+        if !same_program(ast, &formatted) {
+            eprintln!("{}: formatting would change the program", path);
+            return 2;
+        }
+        if formatted == source {
+            continue;
+        }
+        if check {
+            println!("Diff in {}", path);
+            code = 1;
+        } else if let Err(err) = fs::write(path, formatted) {
+            eprintln!("{}: {}", path, err);
+            return 2;
+        }
+    }
+    code
+}
+
+// Whether `formatted` parses back to `ast`, wherever its nodes now are.
+fn same_program(mut ast: ast::Program, formatted: &str) -> bool {
+    let Ok(mut reparsed) = parse_zok(formatted) else {
+        return false;
+    };
+    LocationEraser.visit_program_mut(&mut ast);
+    LocationEraser.visit_program_mut(&mut reparsed);
+    ast == reparsed
+}
+
+/// `stats [--rounds N] <file>...` prints the gate counts, proof size and
+/// estimated verification gas of every function. By default the figures
+/// are for `ROUNDS`, the rounds of the emitted verifier. Like `fmt`, it
+/// prints the usage and exits with 2 when no file is named.
+fn stats(args: &[String]) -> i32 {
+    let mut rounds = ROUNDS;
+    let mut paths = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg != "--rounds" {
+            paths.push(arg);
+            continue;
+        }
+        match args.next().map(|n| n.parse()) {
+            Some(Ok(n)) if n > 0 => rounds = n,
+            _ => {
+                eprintln!("--rounds needs a positive number");
+                return 2;
+            }
+        }
+    }
+    if paths.is_empty() {
+        eprintln!("usage: stats [--rounds N] <file>...");
+        return 2;
+    }
+    for path in paths {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("{}: {}", path, err);
+                return 2;
+            }
+        };
+        match parse_zok(&source).and_then(|ast| StatsTraverser::traverse(&ast, rounds)) {
+            Ok(report) => print!("{}", report),
+            Err(err) => {
+                eprintln!("{}: {} at {}", path, err, err.location);
+                return 2;
+            }
+        }
+    }
+    0
+}
+
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Some("fmt") = args.first().map(String::as_str) {
+        process::exit(fmt(&args[1..]));
+    }
+    if let Some("stats") = args.first().map(String::as_str) {
+        process::exit(stats(&args[1..]));
+    }
+    // `vectors` prints a contract that checks the template against the
+    // test vectors; run its `check_*` functions with any Solidity toolchain.
+    if let Some("vectors") = args.first().map(String::as_str) {
+        println!("{}", VCTraverser::vectors(ROUNDS));
+        return;
+    }
+
+    // `--bitsliced` proves `BITSLICE_ROUNDS` rounds at a time.
+    let engine = if args.iter().any(|arg| arg == "--bitsliced") { Engine::Bitsliced } else { Engine::PerRound };
+    let mut options = ProofOptions::new(ROUNDS).with_engine(engine);
+    // `--seed <64 hex digits>` replays the same proof on every run.
+    if let Some(i) = args.iter().position(|arg| arg == "--seed") {
+        match args.get(i + 1).map(<[u8; 32]>::from_hex) {
+            Some(Ok(seed)) => options = options.with_seed(seed),
+            _ => {
+                eprintln!("--seed needs 64 hex digits");
+                process::exit(2);
+            }
+        }
+    }
+    // `--context <hex>` binds the proof to bytes the verifier is given too.
+    if let Some(i) = args.iter().position(|arg| arg == "--context") {
+        match args.get(i + 1).map(hex::decode) {
+            Some(Ok(context)) => options = options.with_context(context),
+            _ => {
+                eprintln!("--context needs hex digits");
+                process::exit(2);
+            }
+        }
+    }
     // let res = parse_zok("contract A { function sum(private uint a, private uint b, private uint c) returns uint { return a + b + c; } }").unwrap();
     let res = parse_zok("contract A { function check(private uint age) returns uint { return age > 19; } }").unwrap();
-    let contracts = VCTraverser::traverse(res.clone()).unwrap();
-    ProofTraverser::traverse(res, "A".to_string(), "check".to_string(), vec![25]).unwrap();
+    let contracts = VCTraverser::traverse(res.clone(), ROUNDS).unwrap();
+    ProofTraverser::traverse(res, "A".to_string(), "check".to_string(), vec![25], options).unwrap();
 
     println!("{}", contracts);
 }
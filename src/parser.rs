@@ -3,3 +3,5 @@ pub use token::Tok;
 pub mod ast;
 pub mod lexer;
 pub mod token;
+#[cfg(test)]
+mod generator;
@@ -33,6 +33,10 @@ pub enum ContractStatementType {
         function_name: String,
         parameters: Vec<Parameter>,
         return_type: Type,
+        // Only a commitment to a `private` return value is revealed. The
+        // whole value is either public or private; `returns public T` is
+        // the same as `returns T`.
+        private_return: bool,
         statements: Vec<Statement>,
     },
 }
@@ -84,6 +88,9 @@ pub enum ExpressionType {
     Identifier {
         value: String,
     },
+    Literal {
+        value: Vec<u8>,
+    },
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -137,6 +144,20 @@ pub enum AssignOperator {
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Type {
-    // Static size
     UInt256,
+    FixedBytes { size: usize },
+    /// `element[len]`, the elements' words one after the other.
+    Array { element: Box<Type>, len: usize },
+}
+
+impl Type {
+    /// Number of 32-bit circuit words a value of this type occupies.
+    /// Bytes are packed big-endian and left-aligned, four per word.
+    pub fn word_len(&self) -> usize {
+        match self {
+            Type::UInt256 => 1,
+            Type::FixedBytes { size } => size.div_ceil(4),
+            Type::Array { element, len } => element.word_len() * len,
+        }
+    }
 }
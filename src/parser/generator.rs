@@ -0,0 +1,248 @@
+//! Random `ast::Program` generator and the `parse(print(ast)) == ast`
+//! round-trip tests over the grammar.
+
+use crate::ast;
+use crate::parse_program;
+use crate::test_support::{binary, located};
+use crate::traverser::visitor::{LocationEraser, VisitorMut};
+use crate::traverser::FmtTraverser;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+const NAMES: [&str; 6] = ["a", "b", "x", "y", "total", "is_ok"];
+
+const OPERATORS: [ast::BinaryOperator; 18] = [
+    ast::BinaryOperator::Add,
+    ast::BinaryOperator::Sub,
+    ast::BinaryOperator::Mul,
+    ast::BinaryOperator::Div,
+    ast::BinaryOperator::Mod,
+    ast::BinaryOperator::And,
+    ast::BinaryOperator::Or,
+    ast::BinaryOperator::Lt,
+    ast::BinaryOperator::Le,
+    ast::BinaryOperator::Gt,
+    ast::BinaryOperator::Ge,
+    ast::BinaryOperator::Eq,
+    ast::BinaryOperator::NotEq,
+    ast::BinaryOperator::BitAnd,
+    ast::BinaryOperator::BitOr,
+    ast::BinaryOperator::BitXor,
+    ast::BinaryOperator::LShift,
+    ast::BinaryOperator::RShift,
+];
+
+/// Builds trees the grammar can produce: a `ReturnStatement` only ends a
+/// function and `else` blocks are never empty.
+pub struct AstGenerator {
+    rng: StdRng,
+}
+
+impl AstGenerator {
+    pub fn new(seed: u64) -> Self {
+        AstGenerator {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    fn name(&mut self) -> String {
+        NAMES[self.rng.gen_range(0..NAMES.len())].to_string()
+    }
+
+    // One in four types is an array, nested arrays included.
+    fn typ(&mut self) -> ast::Type {
+        match self.rng.gen_range(0..4) {
+            0 | 1 => ast::Type::UInt256,
+            2 => ast::Type::FixedBytes {
+                size: self.rng.gen_range(1..=32),
+            },
+            _ => ast::Type::Array {
+                element: Box::new(self.typ()),
+                len: self.rng.gen_range(1..=8),
+            },
+        }
+    }
+
+    pub fn program(&mut self) -> ast::Program {
+        let contracts = (0..self.rng.gen_range(1..3)).map(|_| self.contract()).collect();
+        ast::Program::GlobalStatements(contracts)
+    }
+
+    fn contract(&mut self) -> ast::GlobalStatement {
+        let members = (0..self.rng.gen_range(0..3)).map(|_| self.function()).collect();
+        located(ast::GlobalStatementType::ContractStatement {
+            contract_name: self.name(),
+            members,
+        })
+    }
+
+    fn function(&mut self) -> ast::ContractStatement {
+        let parameters = (0..self.rng.gen_range(0..3)).map(|_| self.parameter()).collect();
+        let mut statements = self.statements(2);
+        statements.push(located(ast::StatementType::ReturnStatement {
+            return_value: self.expression(3),
+        }));
+        located(ast::ContractStatementType::FunctionStatement {
+            function_name: self.name(),
+            parameters,
+            return_type: self.typ(),
+            private_return: self.rng.gen(),
+            statements,
+        })
+    }
+
+    fn parameter(&mut self) -> ast::Parameter {
+        let variable_type = self.typ();
+        let variable = self.name();
+        if self.rng.gen() {
+            located(ast::ParameterType::Private { variable_type, variable })
+        } else {
+            located(ast::ParameterType::Public { variable_type, variable })
+        }
+    }
+
+    fn statements(&mut self, depth: usize) -> Vec<ast::Statement> {
+        (0..self.rng.gen_range(0..3)).map(|_| self.statement(depth)).collect()
+    }
+
+    pub fn statement(&mut self, depth: usize) -> ast::Statement {
+        let kinds = if depth == 0 { 3 } else { 5 };
+        let node = match self.rng.gen_range(0..kinds) {
+            0 => ast::StatementType::Expression {
+                expression: self.expression(3),
+            },
+            1 => ast::StatementType::AssignStatement {
+                left: self.name(),
+                operator: ast::AssignOperator::Assign,
+                right: self.expression(3),
+            },
+            2 => {
+                let variable = if self.rng.gen_range(0..4) == 0 { String::new() } else { self.name() };
+                let default = if !variable.is_empty() && self.rng.gen() {
+                    Some(self.expression(3))
+                } else {
+                    None
+                };
+                ast::StatementType::InitializerStatement {
+                    variable_type: self.typ(),
+                    variable,
+                    default,
+                }
+            }
+            3 => {
+                let mut else_statements = self.statements(depth - 1);
+                if self.rng.gen() {
+                    else_statements.clear();
+                } else if else_statements.is_empty() {
+                    else_statements.push(self.statement(depth - 1));
+                }
+                ast::StatementType::IfStatement {
+                    condition: self.expression(3),
+                    if_statements: self.statements(depth - 1),
+                    else_statements,
+                }
+            }
+            _ => ast::StatementType::ForEachStatement {
+                iterator: self.name(),
+                iterable: self.expression(2),
+                statements: self.statements(depth - 1),
+            },
+        };
+        located(node)
+    }
+
+    pub fn expression(&mut self, depth: usize) -> ast::Expression {
+        let kinds = if depth == 0 { 3 } else { 5 };
+        let node = match self.rng.gen_range(0..kinds) {
+            0 => ast::ExpressionType::Number { value: self.rng.gen() },
+            1 => ast::ExpressionType::Identifier { value: self.name() },
+            2 => ast::ExpressionType::Literal {
+                value: (0..self.rng.gen_range(0..6)).map(|_| self.rng.gen()).collect(),
+            },
+            3 => ast::ExpressionType::FunctionCallExpression {
+                function_name: self.name(),
+                arguments: (0..self.rng.gen_range(0..3)).map(|_| self.expression(depth - 1)).collect(),
+            },
+            _ => ast::ExpressionType::BinaryExpression {
+                left: Box::new(self.expression(depth - 1)),
+                operator: OPERATORS[self.rng.gen_range(0..OPERATORS.len())].clone(),
+                right: Box::new(self.expression(depth - 1)),
+            },
+        };
+        located(node)
+    }
+}
+
+fn assert_round_trip(program: &ast::Program) {
+    let source = FmtTraverser::traverse(program);
+    let mut parsed = parse_program(&source).unwrap_or_else(|err| panic!("{:?} in\n{}", err, source));
+    LocationEraser.visit_program_mut(&mut parsed);
+    assert_eq!(&parsed, program, "{}", source);
+}
+
+fn program_returning(expr: ast::Expression) -> ast::Program {
+    let function = located(ast::ContractStatementType::FunctionStatement {
+        function_name: "f".to_string(),
+        parameters: vec![],
+        return_type: ast::Type::UInt256,
+        private_return: false,
+        statements: vec![located(ast::StatementType::ReturnStatement { return_value: expr })],
+    });
+    ast::Program::GlobalStatements(vec![located(ast::GlobalStatementType::ContractStatement {
+        contract_name: "A".to_string(),
+        members: vec![function],
+    })])
+}
+
+fn identifier(name: &str) -> ast::Expression {
+    located(ast::ExpressionType::Identifier { value: name.to_string() })
+}
+
+#[test]
+fn test_every_operator_pair_round_trips() {
+    for op1 in OPERATORS.iter() {
+        for op2 in OPERATORS.iter() {
+            let left_nested = binary(binary(identifier("a"), op1, identifier("b")), op2, identifier("c"));
+            let right_nested = binary(identifier("a"), op1, binary(identifier("b"), op2, identifier("c")));
+            assert_round_trip(&program_returning(left_nested));
+            assert_round_trip(&program_returning(right_nested));
+        }
+    }
+}
+
+#[test]
+fn test_comparison_takes_shift_operand() {
+    let mut program = parse_program("contract A { function f() returns uint { return a < b << 1; } }").unwrap();
+    let expected = program_returning(binary(
+        identifier("a"),
+        &ast::BinaryOperator::Lt,
+        binary(
+            identifier("b"),
+            &ast::BinaryOperator::LShift,
+            located(ast::ExpressionType::Number { value: 1 }),
+        ),
+    ));
+    LocationEraser.visit_program_mut(&mut program);
+    assert_eq!(program, expected);
+}
+
+#[test]
+fn test_array_types_round_trip() {
+    let mut program = parse_program("contract A { function f(private uint[3][2] a, bytes4[1] b) returns uint { return a; } }").unwrap();
+    LocationEraser.visit_program_mut(&mut program);
+    assert_round_trip(&program);
+}
+
+#[test]
+fn test_dynamic_bytes_is_not_a_type() {
+    assert!(parse_program("contract A { function f(private bytes a) returns uint { return 1; } }").is_err());
+    assert!(parse_program("contract A { function f() returns bytes { return \"x\"; } }").is_err());
+}
+
+#[test]
+fn test_random_programs_round_trip() {
+    for seed in 0..300 {
+        let program = AstGenerator::new(seed).program();
+        assert_round_trip(&program);
+    }
+}
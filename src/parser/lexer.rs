@@ -11,6 +11,7 @@ pub(crate) struct Lexer<T: Iterator<Item=char>> {
     chars: T,
     location: Location,
     chr: Option<char>,
+    started: bool,
 }
 
 pub(crate) fn make_tokenizer(source: &'_ str) -> impl Iterator<Item=LexResult> + '_ {
@@ -26,10 +27,28 @@ fn keyword_token(key_str: String) -> Tok {
         "if" => Tok::If,
         "else" => Tok::Else,
         "for" => Tok::For,
+        "in" => Tok::In,
         "returns" => Tok::Returns,
         "return" => Tok::Return,
         "private" => Tok::Private,
-        _ => Tok::Identifier { name: key_str }
+        "public" => Tok::Public,
+        "bytes" => Tok::Bytes,
+        _ => match fixed_bytes_size(&key_str) {
+            Some(size) => Tok::FixedBytes { size },
+            None => Tok::Identifier { name: key_str },
+        },
+    }
+}
+
+/// Size of a `bytes1` .. `bytes32` type keyword.
+fn fixed_bytes_size(key_str: &str) -> Option<usize> {
+    let digits = key_str.strip_prefix("bytes")?;
+    if digits.starts_with('0') {
+        return None;
+    }
+    match usize::from_str(digits) {
+        Ok(size) if (1..=32).contains(&size) => Some(size),
+        _ => None,
     }
 }
 
@@ -40,15 +59,18 @@ impl<T> Lexer<T>
     fn new(input: T) -> Self {
         Lexer {
             chars: input,
-            location: Location::new(0, 0),
+            location: Location::start(),
             chr: None,
+            started: false,
         }
     }
 
     fn next_spanned(&mut self) -> LexResult {
-        if self.chr.is_none() {
-            self.next_char();
+        if !self.started {
+            self.started = true;
+            self.chr = self.chars.next();
         }
+        self.skip_blank();
         let start = self.location;
         let tok = self.next_token()?;
         let end = self.location;
@@ -57,29 +79,28 @@ impl<T> Lexer<T>
 
     fn next_token(&mut self) -> Result<Tok, LexicalError> {
         if let Some(c) = self.chr {
-            let token = if self.is_identifier_start(c) {
-                self.consume_identifier(c)?
+            if self.is_identifier_start(c) {
+                self.consume_identifier(c)
             } else {
-                self.consume_special_character(c)?
-            };
-            self.skip_blank();
-            Ok(token)
+                self.consume_special_character(c)
+            }
         } else {
             // End Of File
             Ok(Tok::EOF)
         }
     }
 
+    /// Consumes the current character and moves `location` to the next one,
+    /// so `location` is always the position of `chr`.
     fn next_char(&mut self) {
-        let next = self.chars.next();
-        self.chr = next;
         if let Some(c) = self.chr {
             if c == '\n' {
                 self.location.new_line();
             } else {
-                self.location.go_right();
+                self.location.go_right(c);
             }
         }
+        self.chr = self.chars.next();
     }
 
     fn is_identifier_start(&self, c: char) -> bool {
@@ -87,7 +108,7 @@ impl<T> Lexer<T>
     }
 
     fn is_blank(&self, c: char) -> bool {
-        c == ' ' || c == '\n' || c == '\t'
+        c == ' ' || c == '\n' || c == '\t' || c == '\r'
     }
 
     fn is_identifier_continue(&self, c: char) -> bool {
@@ -153,6 +174,8 @@ impl<T> Lexer<T>
                 "^" => token = Some(Tok::BitXor),
                 "<<" => token = Some(Tok::LShift),
                 ">>" => token = Some(Tok::RShift),
+                // Only valid as the start of `!=`
+                "!" => {}
                 "," => {
                     token = Some(Tok::Comma);
                     break;
@@ -169,6 +192,14 @@ impl<T> Lexer<T>
                     token = Some(Tok::LPar);
                     break;
                 }
+                "[" => {
+                    token = Some(Tok::LSqb);
+                    break;
+                }
+                "]" => {
+                    token = Some(Tok::RSqb);
+                    break;
+                }
                 ")" => {
                     token = Some(Tok::RPar);
                     break;
@@ -258,20 +289,67 @@ impl<T> Lexer<T>
     }
 
     fn lex_literal(&mut self, c: char) -> Result<Tok, LexicalError> {
-        let mut text = String::new();
-        let first = c;
+        let start = self.location;
+        let quote = c;
+        let mut literal = vec![];
         loop {
             self.next_char();
-            if let Some(c) = self.chr {
-                if first == c {
-                    break;
+            match self.chr {
+                Some('\\') => {
+                    let location = self.location;
+                    self.next_char();
+                    let escaped = match self.chr {
+                        Some('n') => b'\n',
+                        Some('t') => b'\t',
+                        Some('r') => b'\r',
+                        Some('0') => b'\0',
+                        Some('\\') => b'\\',
+                        Some('"') => b'"',
+                        Some('\'') => b'\'',
+                        Some('x') => self.lex_hex_escape(location)?,
+                        Some(c) => {
+                            return Err(LexicalError {
+                                error: LexicalErrorType::InvalidEscape(c),
+                                location,
+                            });
+                        }
+                        None => break,
+                    };
+                    literal.push(escaped);
+                }
+                Some('\n') | None => break,
+                Some(c) if c == quote => {
+                    self.next_char();
+                    return Ok(Tok::Literal { literal });
+                }
+                Some(c) => {
+                    let mut buf = [0; 4];
+                    literal.extend(c.encode_utf8(&mut buf).as_bytes());
+                }
+            }
+        }
+        Err(LexicalError {
+            error: LexicalErrorType::UnterminatedString,
+            location: start,
+        })
+    }
+
+    /// Reads the two hex digits of a `\xHH` escape.
+    fn lex_hex_escape(&mut self, location: Location) -> Result<u8, LexicalError> {
+        let mut value = 0;
+        for _ in 0..2 {
+            self.next_char();
+            match self.chr.and_then(|c| c.to_digit(16)) {
+                Some(digit) => value = value * 16 + digit as u8,
+                None => {
+                    return Err(LexicalError {
+                        error: LexicalErrorType::InvalidEscape('x'),
+                        location,
+                    });
                 }
-                text.push(c);
-            } else {
-                break;
             }
         }
-        Ok(Tok::Literal { literal: text })
+        Ok(value)
     }
 }
 
@@ -288,3 +366,117 @@ impl<T> Iterator for Lexer<T>
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lex(source: &str) -> Vec<Spanned> {
+        make_tokenizer(source).map(|t| t.unwrap()).collect()
+    }
+
+    fn loc(row: usize, column: usize, offset: usize) -> Location {
+        Location::new(row, column, offset)
+    }
+
+    #[test]
+    fn test_single_line_positions() {
+        let tokens = lex("contract A { }");
+        assert_eq!(
+            tokens,
+            vec![
+                (loc(1, 1, 0), Tok::Contract, loc(1, 9, 8)),
+                (loc(1, 10, 9), Tok::Identifier { name: "A".to_string() }, loc(1, 11, 10)),
+                (loc(1, 12, 11), Tok::LBrace, loc(1, 13, 12)),
+                (loc(1, 14, 13), Tok::RBrace, loc(1, 15, 14)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_multi_line_positions() {
+        let tokens = lex("  a >= 10\n\tb<<c;\r\n");
+        assert_eq!(
+            tokens,
+            vec![
+                (loc(1, 3, 2), Tok::Identifier { name: "a".to_string() }, loc(1, 4, 3)),
+                (loc(1, 5, 4), Tok::Ge, loc(1, 7, 6)),
+                (loc(1, 8, 7), Tok::Num { number: 10 }, loc(1, 10, 9)),
+                (loc(2, 2, 11), Tok::Identifier { name: "b".to_string() }, loc(2, 3, 12)),
+                (loc(2, 3, 12), Tok::LShift, loc(2, 5, 14)),
+                (loc(2, 5, 14), Tok::Identifier { name: "c".to_string() }, loc(2, 6, 15)),
+                (loc(2, 6, 15), Tok::Semi, loc(2, 7, 16)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_byte_offsets_of_multi_byte_characters() {
+        let tokens = lex("é + 1_000");
+        assert_eq!(
+            tokens,
+            vec![
+                (loc(1, 1, 0), Tok::Identifier { name: "é".to_string() }, loc(1, 2, 2)),
+                (loc(1, 3, 3), Tok::Plus, loc(1, 4, 4)),
+                (loc(1, 5, 5), Tok::Num { number: 1000 }, loc(1, 10, 10)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_literal_escapes() {
+        let tokens = lex(r#"'a\'b' "\x41\n\"é" bytes bytes32 bytes33"#);
+        assert_eq!(
+            tokens,
+            vec![
+                (loc(1, 1, 0), Tok::Literal { literal: b"a'b".to_vec() }, loc(1, 7, 6)),
+                (
+                    loc(1, 8, 7),
+                    Tok::Literal { literal: b"A\n\"\xc3\xa9".to_vec() },
+                    loc(1, 19, 19)
+                ),
+                (loc(1, 20, 20), Tok::Bytes, loc(1, 25, 25)),
+                (loc(1, 26, 26), Tok::FixedBytes { size: 32 }, loc(1, 33, 33)),
+                (loc(1, 34, 34), Tok::Identifier { name: "bytes33".to_string() }, loc(1, 41, 41)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_literal_errors() {
+        assert_eq!(
+            make_tokenizer("a \"abc\ndef\"").nth(1).unwrap(),
+            Err(LexicalError {
+                error: LexicalErrorType::UnterminatedString,
+                location: loc(1, 3, 2),
+            })
+        );
+        assert_eq!(
+            make_tokenizer("'ab\\q'").next().unwrap(),
+            Err(LexicalError {
+                error: LexicalErrorType::InvalidEscape('q'),
+                location: loc(1, 4, 3),
+            })
+        );
+        assert_eq!(
+            make_tokenizer("'\\x4g'").next().unwrap(),
+            Err(LexicalError {
+                error: LexicalErrorType::InvalidEscape('x'),
+                location: loc(1, 2, 1),
+            })
+        );
+    }
+
+    #[test]
+    fn test_error_location() {
+        let mut lexer = make_tokenizer("a\n $");
+        assert!(lexer.next().unwrap().is_ok());
+        assert_eq!(
+            lexer.next().unwrap(),
+            Err(LexicalError {
+                error: LexicalErrorType::UnrecognizedToken('$'),
+                location: loc(2, 2, 3),
+            })
+        );
+    }
+}
@@ -34,6 +34,9 @@ pub enum Tok {
     // Type
     // Static size
     UInt256,
+    FixedBytes { size: usize },
+    // Dynamic size
+    Bytes,
 
     // Keyword
     Function,
@@ -45,9 +48,12 @@ pub enum Tok {
     Returns,
     Return,
     Private,
+    Public,
     // Mark
     LPar,
     RPar,
+    LSqb,
+    RSqb,
     LBrace,
     RBrace,
     Semi,
@@ -55,6 +61,6 @@ pub enum Tok {
     // variable
     Num { number: u64 },
     Identifier { name: String },
-    Literal { literal: String },
+    Literal { literal: Vec<u8> },
     EOF,
 }
@@ -0,0 +1,34 @@
+//! AST builders shared by the parser and traverser tests, and the wire
+//! size of a view for the proof size tests.
+
+use crate::ast;
+use crate::location::Location;
+use crate::parse_program;
+use crate::zkboo::ikos::IKosView;
+
+pub fn located<T>(node: T) -> ast::Located<T> {
+    ast::Located {
+        location: Location::default(),
+        node,
+    }
+}
+
+pub fn binary(left: ast::Expression, operator: &ast::BinaryOperator, right: ast::Expression) -> ast::Expression {
+    located(ast::ExpressionType::BinaryExpression {
+        left: Box::new(left),
+        operator: operator.clone(),
+        right: Box::new(right),
+    })
+}
+
+/// The first function of the first contract in `source`.
+pub fn function_of(source: &str) -> ast::ContractStatement {
+    let ast::Program::GlobalStatements(mut statements) = parse_program(source).unwrap();
+    let ast::GlobalStatementType::ContractStatement { mut members, .. } = statements.remove(0).node;
+    members.remove(0)
+}
+
+/// Size of `view` on the wire, with every word as 4 bytes.
+pub fn view_bytes(view: &IKosView) -> usize {
+    view.rand_tape_seed.len() + view.blinding.len() + 4 * (view.in_data.len() + view.out_data.len())
+}
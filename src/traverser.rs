@@ -1,8 +1,15 @@
 mod vc_traverser;
+mod circuit_traverser;
 mod prf_traverser;
+mod fmt_traverser;
+mod stats_traverser;
+#[cfg(test)]
+mod differential;
+pub mod visitor;
 
-pub use prf_traverser::ProofTraverser;
+pub use circuit_traverser::{CircuitTraverser, COMMITMENT_WORDS, SALT_WORDS};
+pub use fmt_traverser::FmtTraverser;
+pub use prf_traverser::{ProofOptions, ProofTraverser};
+pub use stats_traverser::StatsTraverser;
 pub use vc_traverser::VCTraverser;
-// pub trait Traverser {
-//     fn traverse(&self, ast: ast::Program) -> Vec<dyn Caller>;
-// }
+pub use visitor::{Visitor, VisitorMut};
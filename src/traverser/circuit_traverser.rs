@@ -0,0 +1,686 @@
+use std::collections::HashMap;
+use crate::ast;
+use crate::error::{ZokError, ZokErrorType};
+use crate::location::Location;
+use crate::zkboo::circuit::{Circuit, Gate, Wire, NODE_WORDS};
+use crate::zkboo::utils::convert_u8_to_u32;
+
+type ZokResult<T> = Result<T, ZokError>;
+
+/// Words of the salt a `private` return value is committed with, the
+/// private inputs after those of the parameters.
+pub const SALT_WORDS: usize = 8;
+
+/// Words of the `keccak256` commitment a `private` return value is
+/// revealed as.
+pub const COMMITMENT_WORDS: usize = 8;
+
+/// Compiles one function into a `Circuit`.
+///
+/// Parameters become input words in declaration order: private ones words
+/// of the private input, public ones words of the public input. Branches
+/// are flattened into masks, so under an `if` an assignment or a return
+/// only takes effect where the condition is set. Conditions are used as
+/// masks as they are, which is what comparisons produce.
+///
+/// Arithmetic wraps modulo 2^32. Inside `checked(...)`, `+`, `-` and `*`
+/// also flag overflow, and an `assert(c);` statement flags a condition
+/// that does not hold. The flags of the branches taken are ORed into the
+/// circuit's error word, so a proof shows that none of them was raised.
+///
+/// A `returns private T` function outputs `keccak256` of the return value
+/// and a `bytes32` salt instead of the value, so a proof reveals only the
+/// commitment. Outputs are public or private as a whole: a function that
+/// reveals some words and commits to others is out of scope.
+pub struct CircuitTraverser {
+    circuit: Circuit,
+    vars: HashMap<String, Vec<Wire>>,
+    zero: Option<Wire>,
+    // Mask of the enclosing conditions, `None` outside of any `if`.
+    cond: Option<Wire>,
+    // Set inside `checked(...)`.
+    checked: bool,
+    error: Option<Wire>,
+    // Set by the return; later statements are dead.
+    done: bool,
+    out: Vec<Wire>,
+    output_len: usize,
+}
+
+impl CircuitTraverser {
+    fn new() -> Self {
+        Self {
+            circuit: Circuit::new(),
+            vars: Default::default(),
+            zero: None,
+            cond: None,
+            checked: false,
+            error: None,
+            done: false,
+            out: vec![],
+            output_len: 0,
+        }
+    }
+
+    pub fn traverse(stmt: &ast::ContractStatement) -> ZokResult<Circuit> {
+        let mut zelf = Self::new();
+        match &stmt.node {
+            ast::ContractStatementType::FunctionStatement { function_name: _, parameters, return_type, private_return, statements } => {
+                zelf.output_len = return_type.word_len();
+                for parameter in parameters {
+                    zelf.traverse_parameter(parameter)?;
+                }
+                for statement in statements {
+                    zelf.traverse_statement(statement)?;
+                }
+                let mut out = std::mem::take(&mut zelf.out);
+                let len = zelf.output_len;
+                zelf.resize(&mut out, len);
+                if *private_return {
+                    out.extend((0..SALT_WORDS).map(|_| zelf.circuit.input()));
+                    out = zelf.circuit.keccak256(&out);
+                }
+                zelf.out = out;
+            }
+        }
+        let out = std::mem::take(&mut zelf.out);
+        zelf.circuit.set_outputs(out);
+        if let Some(error) = zelf.error {
+            zelf.circuit.set_error(error);
+        }
+        Ok(zelf.circuit)
+    }
+
+    fn unsupported(location: &Location) -> ZokError {
+        ZokError {
+            error: ZokErrorType::UnsupportedError,
+            location: *location,
+        }
+    }
+
+    fn zero(&mut self) -> Wire {
+        match self.zero {
+            Some(zero) => zero,
+            None => {
+                let zero = self.circuit.constant(0);
+                self.zero = Some(zero);
+                zero
+            }
+        }
+    }
+
+    // Pads with zero words, or truncates, to `len` words.
+    fn resize(&mut self, words: &mut Vec<Wire>, len: usize) {
+        if words.len() < len {
+            let zero = self.zero();
+            words.resize(len, zero);
+        } else {
+            words.truncate(len);
+        }
+    }
+
+    fn traverse_parameter(&mut self, param: &ast::Parameter) -> ZokResult<()> {
+        let (variable_type, variable, public) = match &param.node {
+            ast::ParameterType::Private { variable_type, variable } => (variable_type, variable, false),
+            ast::ParameterType::Public { variable_type, variable } => (variable_type, variable, true),
+        };
+        let len = variable_type.word_len();
+        let words = (0..len)
+            .map(|_| if public { self.circuit.public_input() } else { self.circuit.input() })
+            .collect();
+        self.vars.insert(variable.clone(), words);
+        Ok(())
+    }
+
+    fn traverse_statement(&mut self, stmt: &ast::Statement) -> ZokResult<()> {
+        if self.done {
+            return Ok(());
+        }
+        match &stmt.node {
+            ast::StatementType::IfStatement { condition, if_statements, else_statements } => {
+                let condition = self.traverse_expression(condition)?;
+                let c = match condition.first() {
+                    Some(&c) => c,
+                    None => self.zero(),
+                };
+                let outer = self.cond;
+                self.cond = Some(self.under(outer, c));
+                for statement in if_statements {
+                    self.traverse_statement(statement)?;
+                }
+                if !else_statements.is_empty() {
+                    let not_c = self.circuit.not(c);
+                    self.cond = Some(self.under(outer, not_c));
+                    for statement in else_statements {
+                        self.traverse_statement(statement)?;
+                    }
+                }
+                self.cond = outer;
+            }
+            ast::StatementType::ForEachStatement { .. } => {
+                // TODO: foreach는 vector를 type 추가한 후에 구현 가능
+                return Err(Self::unsupported(&stmt.location));
+            }
+            ast::StatementType::ReturnStatement { return_value } => {
+                // The grammar only has a return at the end of the function.
+                if self.cond.is_some() {
+                    return Err(Self::unsupported(&stmt.location));
+                }
+                let mut value = self.traverse_expression(return_value)?;
+                let len = self.output_len;
+                self.resize(&mut value, len);
+                self.out = value;
+                self.done = true;
+            }
+            ast::StatementType::InitializerStatement { variable_type, variable, default } => {
+                let len = variable_type.word_len();
+                let mut value = match default {
+                    Some(expr) => self.traverse_expression(expr)?,
+                    None => vec![],
+                };
+                self.resize(&mut value, len);
+                self.assign(variable, value);
+            }
+            ast::StatementType::AssignStatement { left, operator: _, right } => {
+                let value = self.traverse_expression(right)?;
+                self.assign(left, value);
+            }
+            // Any other value would be computed and dropped, proving nothing.
+            ast::StatementType::Expression { expression } => match &expression.node {
+                ast::ExpressionType::FunctionCallExpression { function_name, arguments } if function_name == "assert" => {
+                    return self.traverse_assert(arguments, &expression.location);
+                }
+                _ => return Err(ZokError {
+                    error: ZokErrorType::TypeError("only assert is an expression statement".to_string()),
+                    location: expression.location,
+                }),
+            },
+        }
+        Ok(())
+    }
+
+    // Like the condition of an `if`, the asserted value is used as a mask.
+    fn traverse_assert(&mut self, arguments: &[ast::Expression], location: &Location) -> ZokResult<()> {
+        let condition = match arguments {
+            [condition] => self.traverse_expression(condition)?,
+            _ => return Err(ZokError {
+                error: ZokErrorType::TypeError(format!("assert takes 1 argument, not {}", arguments.len())),
+                location: *location,
+            }),
+        };
+        let holds = match condition.first() {
+            Some(&holds) => holds,
+            None => self.zero(),
+        };
+        let fails = self.circuit.not(holds);
+        self.flag(fails);
+        Ok(())
+    }
+
+    // `cond` narrowed by the enclosing mask.
+    fn under(&mut self, outer: Option<Wire>, cond: Wire) -> Wire {
+        match outer {
+            Some(outer) => self.circuit.and(outer, cond),
+            None => cond,
+        }
+    }
+
+    // Records a failed check where the enclosing conditions hold.
+    fn flag(&mut self, flag: Wire) {
+        let flag = match self.cond {
+            Some(mask) => self.circuit.and(mask, flag),
+            None => flag,
+        };
+        self.error = Some(match self.error {
+            Some(error) => self.circuit.or(error, flag),
+            None => flag,
+        });
+    }
+
+    fn assign(&mut self, variable: &str, value: Vec<Wire>) {
+        let value = match self.cond {
+            Some(mask) => {
+                let mut old = self.vars.get(variable).cloned().unwrap_or_default();
+                self.resize(&mut old, value.len());
+                old.into_iter().zip(value)
+                    .map(|(old, new)| self.circuit.mux(mask, old, new))
+                    .collect()
+            }
+            None => value,
+        };
+        self.vars.insert(variable.to_string(), value);
+    }
+
+    fn traverse_expression(&mut self, expr: &ast::Expression) -> ZokResult<Vec<Wire>> {
+        match &expr.node {
+            ast::ExpressionType::BinaryExpression { left, operator, right } => {
+                let v1 = self.traverse_expression(left)?;
+                let v2 = self.traverse_expression(right)?;
+                self.traverse_operator(operator, v1, v2, &expr.location)
+            }
+            ast::ExpressionType::FunctionCallExpression { function_name, arguments } => {
+                self.traverse_call(function_name, arguments, &expr.location)
+            }
+            ast::ExpressionType::Number { value } => {
+                Ok(vec![self.circuit.constant(*value as u32)])
+            }
+            ast::ExpressionType::Identifier { value } => {
+                self.vars.get(value).cloned().ok_or_else(|| ZokError {
+                    error: ZokErrorType::TypeError(format!("unknown variable {}", value)),
+                    location: expr.location,
+                })
+            }
+            ast::ExpressionType::Literal { value } => {
+                Ok(convert_u8_to_u32(value, value.len().div_ceil(4)).into_iter()
+                    .map(|word| self.circuit.constant(word))
+                    .collect())
+            }
+        }
+    }
+
+    // Builtins: `rotr(x, n)` and `rotl(x, n)` rotate every word of `x`,
+    // `checked(e)` is `e` with overflow checks, and `sha256(a, b, ...)` and
+    // `keccak256(a, b, ...)` the digests of the words of their arguments
+    // one after the other. A `bytesN` argument with N not a multiple of 4
+    // is hashed with the zero bytes that pad its last word.
+    // `merkle_verify(root, leaf, path, index_bits)` checks a `bytes32` leaf
+    // against a `bytes32` root through a `bytes32[N]` path, and
+    // `in_range(x, lo, hi)` is `lo <= x && x <= hi` on every word.
+    fn traverse_call(
+        &mut self,
+        function_name: &str,
+        arguments: &[ast::Expression],
+        location: &Location,
+    ) -> ZokResult<Vec<Wire>> {
+        if function_name == "checked" {
+            let arg = match arguments {
+                [arg] => arg,
+                _ => return Err(ZokError {
+                    error: ZokErrorType::TypeError(format!("checked takes 1 argument, not {}", arguments.len())),
+                    location: *location,
+                }),
+            };
+            let outer = std::mem::replace(&mut self.checked, true);
+            let res = self.traverse_expression(arg);
+            self.checked = outer;
+            return res;
+        }
+        let args = arguments.iter()
+            .map(|arg| self.traverse_expression(arg))
+            .collect::<ZokResult<Vec<_>>>()?;
+        match function_name {
+            "rotr" | "rotl" => {
+                let [value, amount] = Self::arguments::<2>(function_name, args, location)?;
+                let left = function_name == "rotl";
+                Ok(self.shift_words(value, amount, |c, w, n| match (c.gates()[n], left) {
+                    (Gate::Const(n), false) => c.rotate_right(w, n),
+                    (Gate::Const(n), true) => c.rotate_right(w, 32 - n % 32),
+                    (_, false) => c.rotate_right_var(w, n),
+                    (_, true) => c.rotate_left_var(w, n),
+                }))
+            }
+            "sha256" => Ok(self.circuit.sha256(&args.concat())),
+            "keccak256" => Ok(self.circuit.keccak256(&args.concat())),
+            "assert" => Err(ZokError {
+                error: ZokErrorType::TypeError("assert is a statement".to_string()),
+                location: *location,
+            }),
+            "in_range" => {
+                let [x, lo, hi] = Self::arguments::<3>(function_name, args, location)?;
+                let len = x.len().max(lo.len()).max(hi.len());
+                let mut words = [x, lo, hi];
+                for word in words.iter_mut() {
+                    self.resize(word, len);
+                }
+                let [x, lo, hi] = words;
+                Ok((0..len).map(|i| self.circuit.in_range(x[i], lo[i], hi[i])).collect())
+            }
+            "merkle_verify" => {
+                let [root, leaf, path, index] = Self::arguments::<4>(function_name, args, location)?;
+                if root.len() != NODE_WORDS || leaf.len() != NODE_WORDS || path.len() % NODE_WORDS != 0 {
+                    return Err(ZokError {
+                        error: ZokErrorType::TypeError("merkle_verify takes a bytes32 root and leaf and a bytes32[N] path".to_string()),
+                        location: *location,
+                    });
+                }
+                // One bit of `index_bits` per level.
+                if path.len() > 32 * NODE_WORDS {
+                    return Err(ZokError {
+                        error: ZokErrorType::TypeError("merkle_verify supports up to 32 levels".to_string()),
+                        location: *location,
+                    });
+                }
+                let index = match index.first() {
+                    Some(&index) => index,
+                    None => self.zero(),
+                };
+                Ok(vec![self.circuit.merkle_verify(&root, &leaf, &path, index)])
+            }
+            // TODO: 이후 구현
+            _ => Err(Self::unsupported(location)),
+        }
+    }
+
+    fn arguments<const N: usize>(
+        function_name: &str,
+        args: Vec<Vec<Wire>>,
+        location: &Location,
+    ) -> ZokResult<[Vec<Wire>; N]> {
+        let len = args.len();
+        args.try_into().map_err(|_| ZokError {
+            error: ZokErrorType::TypeError(format!("{} takes {} arguments, not {}", function_name, N, len)),
+            location: *location,
+        })
+    }
+
+    // Shifts every word of `value` by the first word of `amount`, with
+    // shift gates when the amount is a constant and a barrel shifter
+    // otherwise.
+    fn shift_words<F>(&mut self, value: Vec<Wire>, amount: Vec<Wire>, shift: F) -> Vec<Wire>
+    where
+        F: Fn(&mut Circuit, Wire, Wire) -> Wire,
+    {
+        let amount = match amount.first() {
+            Some(&amount) => amount,
+            None => self.zero(),
+        };
+        value.into_iter().map(|w| shift(&mut self.circuit, w, amount)).collect()
+    }
+
+    // Operands are applied word by word, padding the shorter one with zero
+    // words as a `bytesN` literal is. Equality reduces to a single word;
+    // shifts move every word of the left operand by the right one.
+    fn traverse_operator(
+        &mut self,
+        operator: &ast::BinaryOperator,
+        mut v1: Vec<Wire>,
+        mut v2: Vec<Wire>,
+        location: &Location,
+    ) -> ZokResult<Vec<Wire>> {
+        match operator {
+            ast::BinaryOperator::LShift => {
+                return Ok(self.shift_words(v1, v2, |c, w, n| match c.gates()[n] {
+                    Gate::Const(n) => c.lshift(w, n),
+                    _ => c.lshift_var(w, n),
+                }));
+            }
+            ast::BinaryOperator::RShift => {
+                return Ok(self.shift_words(v1, v2, |c, w, n| match c.gates()[n] {
+                    Gate::Const(n) => c.rshift(w, n),
+                    _ => c.rshift_var(w, n),
+                }));
+            }
+            _ => {}
+        }
+        let len = v1.len().max(v2.len());
+        self.resize(&mut v1, len);
+        self.resize(&mut v2, len);
+        let words = v1.into_iter().zip(v2);
+        if self.checked {
+            let checked: fn(&mut Circuit, Wire, Wire) -> (Wire, Wire) = match operator {
+                ast::BinaryOperator::Add => Circuit::add_checked,
+                ast::BinaryOperator::Sub => Circuit::sub_checked,
+                ast::BinaryOperator::Mul => Circuit::mul_checked,
+                _ => return self.traverse_wrapping(operator, words, location),
+            };
+            let mut res = vec![];
+            for (a, b) in words {
+                let (value, flag) = checked(&mut self.circuit, a, b);
+                self.flag(flag);
+                res.push(value);
+            }
+            return Ok(res);
+        }
+        self.traverse_wrapping(operator, words, location)
+    }
+
+    fn traverse_wrapping<I>(&mut self, operator: &ast::BinaryOperator, words: I, location: &Location) -> ZokResult<Vec<Wire>>
+    where
+        I: Iterator<Item = (Wire, Wire)>,
+    {
+        let c = &mut self.circuit;
+        let res = match operator {
+            ast::BinaryOperator::Eq => {
+                let eqs: Vec<Wire> = words.map(|(a, b)| c.equal(a, b)).collect();
+                let res = match eqs.split_first() {
+                    Some((&first, rest)) => rest.iter().fold(first, |acc, &eq| c.and(acc, eq)),
+                    None => c.constant(u32::MAX),
+                };
+                vec![res]
+            }
+            ast::BinaryOperator::Add => words.map(|(a, b)| c.add(a, b)).collect(),
+            ast::BinaryOperator::Sub => words.map(|(a, b)| c.sub(a, b)).collect(),
+            ast::BinaryOperator::Mul => words.map(|(a, b)| c.mul(a, b)).collect(),
+            ast::BinaryOperator::Gt => words.map(|(a, b)| c.gt(a, b)).collect(),
+            ast::BinaryOperator::Lt => words.map(|(a, b)| c.gt(b, a)).collect(),
+            ast::BinaryOperator::Ge => words.map(|(a, b)| {
+                let lt = c.gt(b, a);
+                c.not(lt)
+            }).collect(),
+            ast::BinaryOperator::Le => words.map(|(a, b)| {
+                let gt = c.gt(a, b);
+                c.not(gt)
+            }).collect(),
+            ast::BinaryOperator::BitAnd => words.map(|(a, b)| c.and(a, b)).collect(),
+            ast::BinaryOperator::BitOr => words.map(|(a, b)| c.or(a, b)).collect(),
+            ast::BinaryOperator::BitXor => words.map(|(a, b)| c.xor(a, b)).collect(),
+            // TODO: 나중에 구현
+            _ => return Err(Self::unsupported(location)),
+        };
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::function_of;
+    use crate::zkboo::ikos::IKosVariable4P;
+    use crate::zkboo::optimizer::optimize;
+    use crypto::digest::Digest;
+    use crypto::sha3::Sha3;
+
+    fn compile(source: &str) -> ZokResult<Circuit> {
+        CircuitTraverser::traverse(&function_of(source))
+    }
+
+    // Evaluates on public constants only, so no gate reads a random tape
+    // and every share holds the plain value.
+    fn run(source: &str, input: &[u32]) -> Vec<u32> {
+        let circuit = compile(source).unwrap();
+        let input: Vec<_> = input.iter().map(|&v| IKosVariable4P::new_value(v)).collect();
+        circuit.eval(&input, &[]).unwrap().iter().map(|v| v.value[0]).collect()
+    }
+
+    #[test]
+    fn test_parameters_become_inputs_in_order() {
+        let circuit = compile(
+            "contract A { function f(private uint a, uint b, private bytes8 c) returns bytes8 { return c ^ a; } }",
+        )
+        .unwrap();
+        assert_eq!(circuit.input_len(), 3);
+        assert_eq!(circuit.input_pub_len(), 1);
+        assert_eq!(circuit.outputs().len(), 2);
+        let stats = circuit.stats();
+        assert_eq!(stats.view_words(), 0);
+        assert_eq!(stats.xor_gates, 2);
+    }
+
+    #[test]
+    fn test_literals_compile_to_packed_words() {
+        // Big-endian bytes, the last word padded with zero bytes.
+        assert_eq!(
+            run("contract A { function f() returns bytes8 { return \"abcde\"; } }", &[]),
+            vec![0x6162_6364, 0x6500_0000],
+        );
+        assert_eq!(
+            run("contract A { function f(private bytes4 a) returns bytes4 { return a ^ \"\\x01\\x02\"; } }", &[0x1000_0000]),
+            vec![0x1102_0000],
+        );
+    }
+
+    #[test]
+    fn test_if_else_selects_by_mask() {
+        let source = "contract A { function f(private uint a) returns uint { uint b = 1; if a == 3 { b = 2; } else { b = 4; }; return b; } }";
+        assert_eq!(run(source, &[3]), vec![2]);
+        assert_eq!(run(source, &[5]), vec![4]);
+    }
+
+    #[test]
+    fn test_nested_conditions_narrow_the_mask() {
+        let source = "contract A { function f(private uint a, private uint b) returns uint { uint c = 0; if a == 1 { if b == 2 { c = 3; } else { c = 4; }; }; return c; } }";
+        assert_eq!(run(source, &[1, 2]), vec![3]);
+        assert_eq!(run(source, &[1, 5]), vec![4]);
+        assert_eq!(run(source, &[0, 2]), vec![0]);
+        // Two comparisons, two narrowed masks and two selections.
+        assert_eq!(compile(source).unwrap().stats().and_gates, 5 + 5 + 2 + 2);
+    }
+
+    #[test]
+    fn test_shifts_and_rotations() {
+        let source = "contract A { function f(private uint a, private uint n) returns uint { return (a << 4) ^ (a >> n) ^ rotr(a, 8) ^ rotl(a, n); } }";
+        let a: u32 = 0x1234_5678;
+        for &n in [0, 3, 31, 32, 100].iter() {
+            let expected = (a << 4) ^ a.checked_shr(n).unwrap_or(0) ^ a.rotate_right(8) ^ a.rotate_left(n);
+            assert_eq!(run(source, &[a, n]), vec![expected]);
+        }
+        // Constant amounts stay local; the secret ones need barrel shifters.
+        let stats = compile(source).unwrap().stats();
+        assert_eq!(stats.and_gates, 11 + 5);
+        let source = "contract A { function f(private uint a) returns uint { return (a >> 40) ^ rotl(a, 8) ^ (a << 1); } }";
+        assert_eq!(run(source, &[a]), vec![a.rotate_left(8) ^ (a << 1)]);
+        assert_eq!(compile(source).unwrap().stats().and_gates, 0);
+    }
+
+    #[test]
+    fn test_checked_arithmetic_sets_the_error_word() {
+        let source = "contract A { function f(private uint balance, private uint amount) returns uint { return checked(balance - amount) + 1; } }";
+        assert_eq!(run(source, &[10, 3]), vec![8, 0]);
+        assert_eq!(run(source, &[3, 10]), vec![3u32.wrapping_sub(10) + 1, u32::MAX]);
+        // Only the subtraction is checked.
+        assert_eq!(run(source, &[u32::MAX, 0]), vec![0, 0]);
+        let circuit = compile(source).unwrap();
+        assert_eq!(circuit.error(), Some(circuit.outputs()[1]));
+
+        let wrapping = "contract A { function f(private uint a, private uint b) returns uint { return a * b - 1; } }";
+        assert_eq!(run(wrapping, &[0x10000, 0x10000]), vec![u32::MAX]);
+        assert_eq!(compile(wrapping).unwrap().error(), None);
+    }
+
+    #[test]
+    fn test_checks_in_branches_not_taken_are_ignored() {
+        let source = "contract A { function f(private uint a, private uint b) returns uint { uint c = 0; if b > a { c = checked(b - a); } else { c = checked(a - b); }; return checked(c * 2); } }";
+        assert_eq!(run(source, &[3, 10]), vec![14, 0]);
+        assert_eq!(run(source, &[10, 3]), vec![14, 0]);
+        assert_eq!(run(source, &[0, 0x8000_0000]), vec![0, u32::MAX]);
+    }
+
+    #[test]
+    fn test_sha256_preimage() {
+        let source = "contract A { function f(private bytes32 secret, bytes32 digest) returns uint { return sha256(secret) == digest; } }";
+        let circuit = compile(source).unwrap();
+        assert_eq!((circuit.input_len(), circuit.input_pub_len()), (8, 8));
+        // SHA-256 of 32 zero bytes.
+        let digest = [
+            0x66687aad, 0xf862bd77, 0x6c8fc18b, 0x8e9f8e20, 0x08971485, 0x6ee233b3, 0x902a591d, 0x0d5f2925,
+        ];
+        let secret: Vec<_> = [0; 8].iter().map(|&v| IKosVariable4P::new_value(v)).collect();
+        let out = circuit.eval(&secret, &digest).unwrap();
+        assert_eq!(out[0].value[0], u32::MAX);
+        let mut wrong = digest;
+        wrong[7] ^= 1;
+        assert_eq!(circuit.eval(&secret, &wrong).unwrap()[0].value[0], 0);
+    }
+
+    #[test]
+    fn test_keccak256_matches_solidity() {
+        // keccak256(abi.encodePacked(uint32(1), uint32(2))) and
+        // keccak256("") in Solidity.
+        let source = "contract A { function f(private uint a, private uint b) returns bytes32 { return keccak256(a, b); } }";
+        let mut keccak = Sha3::keccak256();
+        keccak.input(&[0, 0, 0, 1, 0, 0, 0, 2]);
+        let mut expected = [0; 32];
+        keccak.result(&mut expected);
+        assert_eq!(run(source, &[1, 2]), convert_u8_to_u32(&expected, 8));
+        let source = "contract A { function f() returns bytes32 { return keccak256(); } }";
+        assert_eq!(run(source, &[]), vec![
+            0xc5d24601, 0x86f7233c, 0x927e7db2, 0xdcc703c0, 0xe500b653, 0xca82273b, 0x7bfad804, 0x5d85a470,
+        ]);
+    }
+
+    #[test]
+    fn test_merkle_verify_takes_array_paths() {
+        let source = "contract A { function f(private bytes32 leaf, private bytes32[3] path, private uint index, bytes32 root) returns uint { return merkle_verify(root, leaf, path, index); } }";
+        let circuit = compile(source).unwrap();
+        assert_eq!((circuit.input_len(), circuit.input_pub_len()), (8 + 24 + 1, 8));
+        assert_eq!(circuit.outputs().len(), 1);
+        let err = compile("contract A { function f(private uint leaf, private bytes32[3] path, bytes32 root) returns uint { return merkle_verify(root, leaf, path, 0); } }").unwrap_err();
+        assert_eq!(err.error, ZokErrorType::TypeError("merkle_verify takes a bytes32 root and leaf and a bytes32[N] path".to_string()));
+    }
+
+    #[test]
+    fn test_in_range_and_comparisons() {
+        let source = "contract A { function check(private uint age) returns uint { return in_range(age, 18, 65); } }";
+        for &(age, inside) in [(0, false), (17, false), (18, true), (40, true), (65, true), (66, false), (u32::MAX, false)].iter() {
+            assert_eq!(run(source, &[age]), vec![if inside { u32::MAX } else { 0 }], "age {}", age);
+        }
+        // With constant bounds the first AND of each comparison is a mask.
+        let (optimized, _) = optimize(&compile(source).unwrap());
+        assert_eq!(optimized.stats().and_gates, 21 - 2);
+
+        let source = "contract A { function f(private uint a, private uint b) returns uint { return (a < b) ^ (a >= b) ^ (a <= b); } }";
+        assert_eq!(run(source, &[3, 3]), vec![0]);
+        assert_eq!(run(source, &[2, 3]), vec![0]);
+        assert_eq!(run(source, &[4, 3]), vec![u32::MAX]);
+    }
+
+    #[test]
+    fn test_assertions_set_the_error_word() {
+        let source = "contract A { function f(private uint age, private uint limit) returns uint { assert(age >= 18); if limit > 0 { assert(age <= limit); }; return age + 1; } }";
+        assert_eq!(run(source, &[20, 0]), vec![21, 0]);
+        assert_eq!(run(source, &[20, 30]), vec![21, 0]);
+        assert_eq!(run(source, &[17, 0]), vec![18, u32::MAX]);
+        assert_eq!(run(source, &[20, 19]), vec![21, u32::MAX]);
+
+        let err = compile("contract A { function f(private uint a) returns uint { return assert(a > 1); } }").unwrap_err();
+        assert_eq!(err.error, ZokErrorType::TypeError("assert is a statement".to_string()));
+
+        let err = compile("contract A { function f(private uint a) returns uint { a + 1; return a; } }").unwrap_err();
+        assert_eq!(err.error, ZokErrorType::TypeError("only assert is an expression statement".to_string()));
+        assert_eq!(err.location.column(), 58);
+    }
+
+    #[test]
+    fn test_private_return_reveals_a_commitment() {
+        let source = "contract A { function f(private uint a, uint b) returns private uint { assert(a > b); return a * 2; } }";
+        let circuit = compile(source).unwrap();
+        assert_eq!(circuit.stats().inputs, 1 + SALT_WORDS);
+        assert_eq!(circuit.outputs().len(), COMMITMENT_WORDS + 1);
+
+        let salt: Vec<u32> = (0..SALT_WORDS as u32).map(|i| 0x5a17_0000 + i).collect();
+        let mut input = vec![IKosVariable4P::new_value(21)];
+        input.extend(salt.iter().map(|&w| IKosVariable4P::new_value(w)));
+        let out: Vec<_> = circuit.eval(&input, &[20]).unwrap().iter().map(|v| v.value[0]).collect();
+
+        // keccak256(abi.encodePacked(uint32(42), salt))
+        let mut bytes = 42u32.to_be_bytes().to_vec();
+        bytes.extend(salt.iter().flat_map(|w| w.to_be_bytes()));
+        let mut keccak = Sha3::keccak256();
+        keccak.input(&bytes);
+        let mut expected = [0; 32];
+        keccak.result(&mut expected);
+        let mut expected = convert_u8_to_u32(&expected, COMMITMENT_WORDS);
+        expected.push(0);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_unsupported_code_is_an_error() {
+        let err = compile("contract A { function f(private uint a) returns uint { return a / 2; } }").unwrap_err();
+        assert_eq!(err.error, ZokErrorType::UnsupportedError);
+        let err = compile("contract A { function f() returns uint { return b; } }").unwrap_err();
+        assert_eq!(err.error, ZokErrorType::TypeError("unknown variable b".to_string()));
+        let err = compile("contract A { function f(private uint a) returns uint { return rotr(a); } }").unwrap_err();
+        assert_eq!(err.error, ZokErrorType::TypeError("rotr takes 2 arguments, not 1".to_string()));
+    }
+}
@@ -0,0 +1,478 @@
+//! Differential tests of the circuit compiler on `uint` functions. A
+//! function is evaluated on plain `u32`s by a reference interpreter, on the
+//! prover's shares and on the verifier's two opened shares, and all three
+//! must agree, for the compiled circuit and for the optimized one.
+//!
+//! The interpreter knows the operators in `OPERATORS` and `rotr`, `rotl`,
+//! `in_range`, `checked` and `assert`. Bytes types, literals, `sha256`,
+//! `keccak256` and `merkle_verify` are left to the vector tests in
+//! `circuit_traverser`.
+
+use crate::ast;
+use crate::test_support::{binary, function_of, located};
+use crate::traverser::{CircuitTraverser, FmtTraverser};
+use crate::zkboo::circuit::Circuit;
+use crate::zkboo::ikos::{IKosContext, IKosVariable4P, IKosVariable4V, MasterSeeder, SharedContexts, RAND_TAPE_SEED_LEN};
+use crate::zkboo::optimizer::optimize;
+use crate::zkboo::zkboo::{ProvingProof, VerifyingProof, ZkBoo};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+
+/// Operators the circuit compiles.
+const OPERATORS: [ast::BinaryOperator; 13] = [
+    ast::BinaryOperator::Add,
+    ast::BinaryOperator::Sub,
+    ast::BinaryOperator::Mul,
+    ast::BinaryOperator::Lt,
+    ast::BinaryOperator::Le,
+    ast::BinaryOperator::Gt,
+    ast::BinaryOperator::Ge,
+    ast::BinaryOperator::Eq,
+    ast::BinaryOperator::BitAnd,
+    ast::BinaryOperator::BitOr,
+    ast::BinaryOperator::BitXor,
+    ast::BinaryOperator::LShift,
+    ast::BinaryOperator::RShift,
+];
+
+const COMPARISONS: [ast::BinaryOperator; 5] = [
+    ast::BinaryOperator::Lt,
+    ast::BinaryOperator::Le,
+    ast::BinaryOperator::Gt,
+    ast::BinaryOperator::Ge,
+    ast::BinaryOperator::Eq,
+];
+
+/// Values around the carries, the sign bit and the shift widths.
+const EDGES: [u32; 10] = [0, 1, 2, 31, 32, 33, 0x7fff_ffff, 0x8000_0000, 0xffff_fffe, u32::MAX];
+
+fn mask(b: bool) -> u32 {
+    if b { u32::MAX } else { 0 }
+}
+
+/// Reference semantics of a `uint` function: arithmetic wraps modulo
+/// 2^32, comparisons give all ones or zero, shifts by 32 or more give
+/// zero and rotations take the amount modulo 32. A failed check in a
+/// branch taken sets the error word, which is output after the return
+/// value if the function has any check.
+#[derive(Default)]
+struct Interpreter {
+    vars: HashMap<String, u32>,
+    has_checks: bool,
+    failed: bool,
+    out: u32,
+}
+
+impl Interpreter {
+    fn run(function: &ast::ContractStatement, input: &[u32], input_pub: &[u32]) -> Vec<u32> {
+        let mut zelf = Self::default();
+        let ast::ContractStatementType::FunctionStatement { parameters, statements, .. } = &function.node;
+        let (mut input, mut input_pub) = (input.iter(), input_pub.iter());
+        for param in parameters {
+            let (variable, value) = match &param.node {
+                ast::ParameterType::Private { variable, .. } => (variable, input.next()),
+                ast::ParameterType::Public { variable, .. } => (variable, input_pub.next()),
+            };
+            zelf.vars.insert(variable.clone(), *value.unwrap());
+        }
+        for statement in statements {
+            zelf.exec(statement, true);
+        }
+        let mut out = vec![zelf.out];
+        if zelf.has_checks {
+            out.push(mask(zelf.failed));
+        }
+        out
+    }
+
+    // Statements of branches not taken are still walked, as the circuit
+    // compiles them, but change nothing.
+    fn exec(&mut self, stmt: &ast::Statement, taken: bool) {
+        match &stmt.node {
+            ast::StatementType::IfStatement { condition, if_statements, else_statements } => {
+                let c = self.eval(condition, taken, false) != 0;
+                for statement in if_statements {
+                    self.exec(statement, taken && c);
+                }
+                for statement in else_statements {
+                    self.exec(statement, taken && !c);
+                }
+            }
+            ast::StatementType::ReturnStatement { return_value } => {
+                self.out = self.eval(return_value, taken, false);
+            }
+            ast::StatementType::InitializerStatement { variable, default, .. } => {
+                let value = match default {
+                    Some(expr) => self.eval(expr, taken, false),
+                    None => 0,
+                };
+                self.set(variable, value, taken);
+            }
+            ast::StatementType::AssignStatement { left, right, .. } => {
+                let value = self.eval(right, taken, false);
+                self.set(left, value, taken);
+            }
+            ast::StatementType::Expression { expression } => {
+                if let ast::ExpressionType::FunctionCallExpression { function_name, arguments } = &expression.node {
+                    if function_name == "assert" {
+                        let holds = self.eval(&arguments[0], taken, false);
+                        self.check(holds == 0, taken);
+                    }
+                }
+            }
+            ast::StatementType::ForEachStatement { .. } => unreachable!(),
+        }
+    }
+
+    // A variable first set in a branch not taken is zero.
+    fn set(&mut self, variable: &str, value: u32, taken: bool) {
+        if taken {
+            self.vars.insert(variable.to_string(), value);
+        } else {
+            self.vars.entry(variable.to_string()).or_insert(0);
+        }
+    }
+
+    fn check(&mut self, failed: bool, taken: bool) {
+        self.has_checks = true;
+        self.failed |= failed && taken;
+    }
+
+    fn eval(&mut self, expr: &ast::Expression, taken: bool, checked: bool) -> u32 {
+        match &expr.node {
+            ast::ExpressionType::BinaryExpression { left, operator, right } => {
+                let a = self.eval(left, taken, checked);
+                let b = self.eval(right, taken, checked);
+                match operator {
+                    ast::BinaryOperator::Add => {
+                        let (sum, overflow) = a.overflowing_add(b);
+                        if checked {
+                            self.check(overflow, taken);
+                        }
+                        sum
+                    }
+                    ast::BinaryOperator::Sub => {
+                        let (diff, underflow) = a.overflowing_sub(b);
+                        if checked {
+                            self.check(underflow, taken);
+                        }
+                        diff
+                    }
+                    ast::BinaryOperator::Mul => {
+                        if checked {
+                            self.check(a as u64 * b as u64 > u32::MAX as u64, taken);
+                        }
+                        a.wrapping_mul(b)
+                    }
+                    ast::BinaryOperator::Lt => mask(a < b),
+                    ast::BinaryOperator::Le => mask(a <= b),
+                    ast::BinaryOperator::Gt => mask(a > b),
+                    ast::BinaryOperator::Ge => mask(a >= b),
+                    ast::BinaryOperator::Eq => mask(a == b),
+                    ast::BinaryOperator::BitAnd => a & b,
+                    ast::BinaryOperator::BitOr => a | b,
+                    ast::BinaryOperator::BitXor => a ^ b,
+                    ast::BinaryOperator::LShift => a.checked_shl(b).unwrap_or(0),
+                    ast::BinaryOperator::RShift => a.checked_shr(b).unwrap_or(0),
+                    _ => unreachable!(),
+                }
+            }
+            ast::ExpressionType::FunctionCallExpression { function_name, arguments } => {
+                if function_name == "checked" {
+                    return self.eval(&arguments[0], taken, true);
+                }
+                let args: Vec<u32> = arguments.iter().map(|arg| self.eval(arg, taken, checked)).collect();
+                match function_name.as_str() {
+                    "rotr" => args[0].rotate_right(args[1] % 32),
+                    "rotl" => args[0].rotate_left(args[1] % 32),
+                    "in_range" => mask(args[1] <= args[0] && args[0] <= args[2]),
+                    _ => unreachable!(),
+                }
+            }
+            ast::ExpressionType::Number { value } => *value as u32,
+            ast::ExpressionType::Identifier { value } => self.vars[value],
+            ast::ExpressionType::Literal { .. } => unreachable!(),
+        }
+    }
+}
+
+// Splits `value` into three random shares on the given contexts.
+fn share(value: u32, ctx: &SharedContexts, rng: &mut StdRng) -> IKosVariable4P {
+    let (r0, r1): (u32, u32) = (rng.gen(), rng.gen());
+    IKosVariable4P::new_share(vec![r0, r1, value ^ r0 ^ r1], ctx.clone())
+}
+
+/// Runs `circuit` on shares of `input` as the prover does, then replays
+/// the first two parties as the verifier does from their tapes and the
+/// second party's view. Returns the output the prover reconstructs from
+/// its three shares and the one the verifier reconstructs from its two
+/// shares and the third share the proof sends.
+fn run_shared(circuit: &Circuit, input: &[u32], input_pub: &[u32], rng: &mut StdRng) -> (Vec<u32>, Vec<u32>) {
+    let seeds: Vec<Vec<u8>> = (0..3).map(|_| (0..RAND_TAPE_SEED_LEN).map(|_| rng.gen()).collect()).collect();
+    let ctx = SharedContexts::new(seeds.iter().map(|s| IKosContext::new_with_seed(s.clone(), vec![])).collect());
+    let shared: Vec<_> = input.iter().map(|&v| share(v, &ctx, rng)).collect();
+    let out_4p = circuit.eval(&shared, input_pub).unwrap();
+    let prover = out_4p.iter().map(|v| v.value.iter().fold(0, |acc, share| acc ^ share)).collect();
+
+    let mut views: Vec<_> = seeds[..2].iter().map(|s| IKosContext::new_with_seed(s.clone(), vec![])).collect();
+    views[1].ikos_view.out_data = ctx.lock()[1].ikos_view.out_data.clone();
+    let opened = SharedContexts::new(views);
+    let shared: Vec<_> = shared.iter().map(|v| IKosVariable4V::new_share(v.value[..2].to_vec(), opened.clone())).collect();
+    let out_4v = circuit.eval(&shared, input_pub).unwrap();
+    let verifier = out_4v.iter().zip(&out_4p).map(|(v, p)| {
+        assert_eq!(v.value, p.value[..2].to_vec(), "verifier shares differ from the prover's");
+        v.value[0] ^ v.value[1] ^ p.value[2]
+    }).collect();
+    (prover, verifier)
+}
+
+fn input_value(rng: &mut StdRng) -> u32 {
+    match rng.gen_range(0..3) {
+        0 => EDGES[rng.gen_range(0..EDGES.len())],
+        1 => rng.gen_range(0..40),
+        _ => rng.gen(),
+    }
+}
+
+/// Checks `function` on `runs` random inputs, and proves and verifies it
+/// end to end on the first one.
+fn assert_agrees(function: &ast::ContractStatement, runs: usize, rng: &mut StdRng) {
+    let source = FmtTraverser::traverse_function("A", function);
+    let compiled = CircuitTraverser::traverse(function).unwrap_or_else(|err| panic!("{:?} in\n{}", err, source));
+    let (optimized, _) = optimize(&compiled);
+    let ast::ContractStatementType::FunctionStatement { parameters, .. } = &function.node;
+    let private = parameters.iter().filter(|p| matches!(p.node, ast::ParameterType::Private { .. })).count();
+
+    for run in 0..runs {
+        let input: Vec<u32> = (0..private).map(|_| input_value(rng)).collect();
+        let input_pub: Vec<u32> = (private..parameters.len()).map(|_| input_value(rng)).collect();
+        let expected = Interpreter::run(function, &input, &input_pub);
+        for circuit in [&compiled, &optimized].iter() {
+            let (prover, verifier) = run_shared(circuit, &input, &input_pub, rng);
+            assert_eq!(prover, expected, "prover on {:?} {:?} in\n{}", input, input_pub, source);
+            assert_eq!(verifier, expected, "verifier on {:?} {:?} in\n{}", input, input_pub, source);
+        }
+
+        if run > 0 {
+            continue;
+        }
+        let zk_boo = ZkBoo::new(4, 3, 2, 32);
+        let proving = ProvingProof::new(input.clone(), input_pub.clone(), expected.len(), Box::new(optimized.clone()))
+            .with_seeder(Box::new(MasterSeeder::new(rng.gen())));
+        let accepted = expected.len() == 1 || expected[1] == 0;
+        let mut proof = match zk_boo.prove(proving) {
+            Ok(proof) if accepted => proof,
+            Err(_) if !accepted => continue,
+            res => panic!("prove gave {:?} for {:?} {:?} in\n{}", res.map(|p| p.output), input, input_pub, source),
+        };
+        assert_eq!(proof.output, expected, "proof on {:?} {:?} in\n{}", input, input_pub, source);
+        let challenge = proof.challenge();
+        let response = zk_boo.build_response(&proof, &challenge);
+        let two_views = zk_boo.rebuild_proof(&mut proof, &challenge);
+        let verifying = VerifyingProof::new(proof.input_len, input_pub, proof.output.clone(), challenge, two_views, response, Box::new(optimized.clone()));
+        assert_eq!(zk_boo.verify(verifying), Ok(true), "{}", source);
+    }
+}
+
+/// Builds `uint` functions out of the operators, builtins and statements
+/// the circuit compiles. Conditions and asserted values are comparisons
+/// or bitwise combinations of them, so they are all ones or zero.
+struct ProgramGenerator {
+    rng: StdRng,
+    // Variables in scope.
+    names: Vec<String>,
+    next: usize,
+}
+
+impl ProgramGenerator {
+    fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            names: vec![],
+            next: 0,
+        }
+    }
+
+    fn fresh(&mut self, prefix: &str) -> String {
+        self.next += 1;
+        format!("{}{}", prefix, self.next)
+    }
+
+    fn function(&mut self) -> ast::ContractStatement {
+        let mut parameters = vec![];
+        for _ in 0..self.rng.gen_range(1..=3) {
+            let variable = self.fresh("p");
+            self.names.push(variable.clone());
+            let variable_type = ast::Type::UInt256;
+            parameters.push(located(if self.rng.gen() {
+                ast::ParameterType::Private { variable_type, variable }
+            } else {
+                ast::ParameterType::Public { variable_type, variable }
+            }));
+        }
+        // The interpreter reads private inputs and public ones in order.
+        parameters.sort_by_key(|p| matches!(p.node, ast::ParameterType::Public { .. }));
+        let mut statements = self.statements(2);
+        statements.push(located(ast::StatementType::ReturnStatement {
+            return_value: self.expression(3),
+        }));
+        located(ast::ContractStatementType::FunctionStatement {
+            function_name: "f".to_string(),
+            parameters,
+            return_type: ast::Type::UInt256,
+            private_return: false,
+            statements,
+        })
+    }
+
+    // Variables declared in a block go out of scope at its end.
+    fn statements(&mut self, depth: usize) -> Vec<ast::Statement> {
+        let scope = self.names.len();
+        let statements = (0..self.rng.gen_range(1..4)).map(|_| self.statement(depth)).collect();
+        self.names.truncate(scope);
+        statements
+    }
+
+    fn statement(&mut self, depth: usize) -> ast::Statement {
+        let kinds = if depth == 0 { 3 } else { 4 };
+        let node = match self.rng.gen_range(0..kinds) {
+            0 => {
+                let default = if self.rng.gen_range(0..4) > 0 { Some(self.expression(2)) } else { None };
+                let variable = self.fresh("v");
+                self.names.push(variable.clone());
+                ast::StatementType::InitializerStatement {
+                    variable_type: ast::Type::UInt256,
+                    variable,
+                    default,
+                }
+            }
+            1 => ast::StatementType::AssignStatement {
+                left: self.names[self.rng.gen_range(0..self.names.len())].clone(),
+                operator: ast::AssignOperator::Assign,
+                right: self.expression(2),
+            },
+            2 => ast::StatementType::Expression {
+                expression: located(ast::ExpressionType::FunctionCallExpression {
+                    function_name: "assert".to_string(),
+                    arguments: vec![self.condition(2)],
+                }),
+            },
+            _ => {
+                let else_statements = if self.rng.gen() { self.statements(depth - 1) } else { vec![] };
+                ast::StatementType::IfStatement {
+                    condition: self.condition(2),
+                    if_statements: self.statements(depth - 1),
+                    else_statements,
+                }
+            }
+        };
+        located(node)
+    }
+
+    fn number(&mut self) -> ast::Expression {
+        let value = if self.rng.gen() {
+            EDGES[self.rng.gen_range(0..EDGES.len())]
+        } else {
+            self.rng.gen_range(0..40)
+        };
+        located(ast::ExpressionType::Number { value: value as u64 })
+    }
+
+    fn call(&mut self, function_name: &str, arguments: Vec<ast::Expression>) -> ast::Expression {
+        located(ast::ExpressionType::FunctionCallExpression {
+            function_name: function_name.to_string(),
+            arguments,
+        })
+    }
+
+    fn expression(&mut self, depth: usize) -> ast::Expression {
+        if depth == 0 || self.rng.gen_range(0..4) == 0 {
+            return if self.rng.gen_range(0..3) == 0 {
+                self.number()
+            } else {
+                let name = self.names[self.rng.gen_range(0..self.names.len())].clone();
+                located(ast::ExpressionType::Identifier { value: name })
+            };
+        }
+        match self.rng.gen_range(0..8) {
+            0 => {
+                let arg = self.expression(depth - 1);
+                self.call("checked", vec![arg])
+            }
+            1 => {
+                let function_name = if self.rng.gen() { "rotr" } else { "rotl" };
+                let (value, amount) = (self.expression(depth - 1), self.expression(depth - 1));
+                self.call(function_name, vec![value, amount])
+            }
+            2 => self.condition(depth),
+            _ => {
+                let operator = OPERATORS[self.rng.gen_range(0..OPERATORS.len())].clone();
+                let (left, right) = (self.expression(depth - 1), self.expression(depth - 1));
+                binary(left, &operator, right)
+            }
+        }
+    }
+
+    fn condition(&mut self, depth: usize) -> ast::Expression {
+        match self.rng.gen_range(0..if depth == 0 { 2 } else { 3 }) {
+            0 => {
+                let operator = COMPARISONS[self.rng.gen_range(0..COMPARISONS.len())].clone();
+                let (left, right) = (self.expression(depth.min(1)), self.expression(depth.min(1)));
+                binary(left, &operator, right)
+            }
+            1 => {
+                let args = (0..3).map(|_| self.expression(depth.min(1))).collect();
+                self.call("in_range", args)
+            }
+            _ => {
+                let operator = [ast::BinaryOperator::BitAnd, ast::BinaryOperator::BitOr, ast::BinaryOperator::BitXor]
+                    [self.rng.gen_range(0..3)].clone();
+                let (left, right) = (self.condition(depth - 1), self.condition(depth - 1));
+                binary(left, &operator, right)
+            }
+        }
+    }
+}
+
+#[test]
+fn test_every_operator_agrees() {
+    let mut rng = StdRng::seed_from_u64(7);
+    for operator in OPERATORS.iter() {
+        let op = FmtTraverser::traverse_operator(operator);
+        // Private and public operands, and a constant one that the circuit
+        // compiles into shift and rotation gates rather than gadgets.
+        for source in [
+            format!("contract A {{ function f(private uint a, uint b) returns uint {{ return a {} b; }} }}", op),
+            format!("contract A {{ function f(private uint a, private uint b) returns uint {{ return checked(a {} b); }} }}", op),
+            format!("contract A {{ function f(uint a) returns uint {{ return a {} 5; }} }}", op),
+        ].iter() {
+            assert_agrees(&function_of(source), 40, &mut rng);
+        }
+    }
+}
+
+#[test]
+fn test_every_statement_agrees() {
+    let sources = [
+        "contract A { function f(private uint a, private uint b) returns uint { return rotr(a, b) ^ rotl(a, 7) ^ rotr(b, 33); } }",
+        "contract A { function f(private uint a, uint lo, uint hi) returns uint { return in_range(a, lo, hi); } }",
+        "contract A { function f(private uint a) returns uint { uint x; uint y = a * 3; x = y - a; return x; } }",
+        "contract A { function f(private uint a, uint b) returns uint { assert(a > b); return a - b; } }",
+        "contract A { function f(private uint a, private uint b) returns uint { uint r = 1; if a > b { r = a; if checked(a - b) > 10 { r = 2; } else { assert(b == 0); }; } else { r = b; }; return r; } }",
+        "contract A { function f(private uint a, private uint b) returns uint { uint r = a; if a == b { assert(a < 5); r = checked(a * b); }; return r; } }",
+    ];
+    let mut rng = StdRng::seed_from_u64(11);
+    for source in sources.iter() {
+        assert_agrees(&function_of(source), 40, &mut rng);
+    }
+}
+
+#[test]
+fn test_random_programs_agree() {
+    let mut rng = StdRng::seed_from_u64(13);
+    for seed in 0..60 {
+        let function = ProgramGenerator::new(seed).function();
+        assert_agrees(&function, 6, &mut rng);
+    }
+}
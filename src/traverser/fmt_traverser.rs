@@ -0,0 +1,360 @@
+use crate::ast;
+use crate::traverser::Visitor;
+
+const INDENT: &str = "    ";
+
+/// Prints an `ast::Program` back into canonical `.zok` source.
+///
+/// Binary expressions are parenthesized only where the grammar's
+/// precedence ladder requires it, so `parse(print(ast))` gives back the
+/// same tree apart from locations.
+pub struct FmtTraverser {
+    out: String,
+    depth: usize,
+}
+
+/// Binding strength of each level of the expression grammar, from
+/// `LogicalOrExpression` (loosest) to `Value` (tightest).
+fn precedence(op: &ast::BinaryOperator) -> usize {
+    match op {
+        ast::BinaryOperator::Or => 1,
+        ast::BinaryOperator::And => 2,
+        ast::BinaryOperator::BitOr => 3,
+        ast::BinaryOperator::BitXor => 4,
+        ast::BinaryOperator::BitAnd => 5,
+        ast::BinaryOperator::Eq | ast::BinaryOperator::NotEq => 6,
+        ast::BinaryOperator::Lt
+        | ast::BinaryOperator::Le
+        | ast::BinaryOperator::Gt
+        | ast::BinaryOperator::Ge => 7,
+        ast::BinaryOperator::LShift | ast::BinaryOperator::RShift => 8,
+        ast::BinaryOperator::Add | ast::BinaryOperator::Sub => 9,
+        ast::BinaryOperator::Mul | ast::BinaryOperator::Div | ast::BinaryOperator::Mod => 10,
+    }
+}
+
+const VALUE_PRECEDENCE: usize = 11;
+
+impl FmtTraverser {
+    fn new() -> Self {
+        Self {
+            out: String::new(),
+            depth: 0,
+        }
+    }
+
+    pub fn traverse(ast: &ast::Program) -> String {
+        let mut zelf = Self::new();
+        zelf.visit_program(ast);
+        zelf.out
+    }
+
+    /// Canonical source of `function` alone inside its contract, the
+    /// input of a proof's circuit digest.
+    pub fn traverse_function(contract_name: &str, function: &ast::ContractStatement) -> String {
+        let contract = ast::Located {
+            location: Default::default(),
+            node: ast::GlobalStatementType::ContractStatement {
+                contract_name: contract_name.to_string(),
+                members: vec![function.clone()],
+            },
+        };
+        Self::traverse(&ast::Program::GlobalStatements(vec![contract]))
+    }
+
+    fn line(&mut self, s: &str) {
+        for _ in 0..self.depth {
+            self.out.push_str(INDENT);
+        }
+        self.out.push_str(s);
+        self.out.push('\n');
+    }
+
+    fn traverse_parameter(param: &ast::Parameter) -> String {
+        match &param.node {
+            ast::ParameterType::Private { variable_type, variable } => {
+                format!("private {} {}", Self::traverse_type(variable_type), variable)
+            }
+            ast::ParameterType::Public { variable_type, variable } => {
+                format!("{} {}", Self::traverse_type(variable_type), variable)
+            }
+        }
+    }
+
+    pub fn traverse_type(typ: &ast::Type) -> String {
+        match typ {
+            ast::Type::UInt256 => "uint".to_string(),
+            ast::Type::FixedBytes { size } => format!("bytes{}", size),
+            ast::Type::Array { element, len } => format!("{}[{}]", Self::traverse_type(element), len),
+        }
+    }
+
+    fn traverse_block(&mut self, statements: &[ast::Statement]) {
+        self.depth += 1;
+        for statement in statements {
+            self.visit_statement(statement);
+        }
+        self.depth -= 1;
+    }
+
+    pub fn traverse_expression(expr: &ast::Expression) -> String {
+        Self::traverse_expression_with(expr, 0)
+    }
+
+    // `min` is the loosest precedence allowed here without parentheses.
+    fn traverse_expression_with(expr: &ast::Expression, min: usize) -> String {
+        let (s, prec) = match &expr.node {
+            ast::ExpressionType::BinaryExpression { left, operator, right } => {
+                let prec = precedence(operator);
+                // All levels are left-associative.
+                let l = Self::traverse_expression_with(left, prec);
+                let r = Self::traverse_expression_with(right, prec + 1);
+                (format!("{} {} {}", l, Self::traverse_operator(operator), r), prec)
+            }
+            ast::ExpressionType::FunctionCallExpression { function_name, arguments } => {
+                let args: Vec<String> = arguments.iter().map(Self::traverse_expression).collect();
+                (format!("{}({})", function_name, args.join(", ")), VALUE_PRECEDENCE)
+            }
+            ast::ExpressionType::Number { value } => (value.to_string(), VALUE_PRECEDENCE),
+            ast::ExpressionType::Identifier { value } => (value.clone(), VALUE_PRECEDENCE),
+            ast::ExpressionType::Literal { value } => (Self::traverse_literal(value), VALUE_PRECEDENCE),
+        };
+        if prec < min {
+            format!("({})", s)
+        } else {
+            s
+        }
+    }
+
+    fn traverse_literal(value: &[u8]) -> String {
+        let mut s = String::from("\"");
+        for &byte in value {
+            match byte {
+                b'\n' => s.push_str("\\n"),
+                b'\t' => s.push_str("\\t"),
+                b'\r' => s.push_str("\\r"),
+                b'\0' => s.push_str("\\0"),
+                b'\\' => s.push_str("\\\\"),
+                b'"' => s.push_str("\\\""),
+                0x20..=0x7e => s.push(byte as char),
+                _ => s.push_str(format!("\\x{:02x}", byte).as_str()),
+            }
+        }
+        s.push('"');
+        s
+    }
+
+    pub fn traverse_operator(op: &ast::BinaryOperator) -> &'static str {
+        match op {
+            ast::BinaryOperator::Add => "+",
+            ast::BinaryOperator::Sub => "-",
+            ast::BinaryOperator::Mul => "*",
+            ast::BinaryOperator::Div => "/",
+            ast::BinaryOperator::Mod => "%",
+            ast::BinaryOperator::And => "&&",
+            ast::BinaryOperator::Or => "||",
+            ast::BinaryOperator::Lt => "<",
+            ast::BinaryOperator::Le => "<=",
+            ast::BinaryOperator::Gt => ">",
+            ast::BinaryOperator::Ge => ">=",
+            ast::BinaryOperator::Eq => "==",
+            ast::BinaryOperator::NotEq => "!=",
+            ast::BinaryOperator::BitAnd => "&",
+            ast::BinaryOperator::BitOr => "|",
+            ast::BinaryOperator::BitXor => "^",
+            ast::BinaryOperator::LShift => "<<",
+            ast::BinaryOperator::RShift => ">>",
+        }
+    }
+}
+
+// Expressions are printed by `traverse_expression`, which needs the
+// precedence of the enclosing operator, so only programs, contracts,
+// functions and statements are visited.
+impl Visitor for FmtTraverser {
+    fn visit_program(&mut self, program: &ast::Program) {
+        match program {
+            ast::Program::GlobalStatements(v) => {
+                for (i, gs) in v.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push('\n');
+                    }
+                    self.visit_global_statement(gs);
+                }
+            }
+        }
+    }
+
+    fn visit_global_statement(&mut self, stmt: &ast::GlobalStatement) {
+        match &stmt.node {
+            ast::GlobalStatementType::ContractStatement { contract_name, members } => {
+                self.line(format!("contract {} {{", contract_name).as_str());
+                self.depth += 1;
+                for (i, member) in members.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push('\n');
+                    }
+                    self.visit_contract_statement(member);
+                }
+                self.depth -= 1;
+                self.line("}");
+            }
+        }
+    }
+
+    fn visit_contract_statement(&mut self, stmt: &ast::ContractStatement) {
+        match &stmt.node {
+            ast::ContractStatementType::FunctionStatement { function_name, parameters, return_type, private_return, statements } => {
+                let params: Vec<String> = parameters.iter().map(Self::traverse_parameter).collect();
+                self.line(format!(
+                    "function {}({}) returns {}{} {{",
+                    function_name,
+                    params.join(", "),
+                    if *private_return { "private " } else { "" },
+                    Self::traverse_type(return_type)
+                ).as_str());
+                self.traverse_block(statements);
+                self.line("}");
+            }
+        }
+    }
+
+    // Every statement, including `if` and `for`, is terminated by `;`.
+    fn visit_statement(&mut self, stmt: &ast::Statement) {
+        match &stmt.node {
+            ast::StatementType::IfStatement { condition, if_statements, else_statements } => {
+                let cond = Self::traverse_expression(condition);
+                self.line(format!("if {} {{", cond).as_str());
+                self.traverse_block(if_statements);
+                if else_statements.is_empty() {
+                    self.line("};");
+                } else {
+                    self.line("} else {");
+                    self.traverse_block(else_statements);
+                    self.line("};");
+                }
+            }
+            ast::StatementType::ForEachStatement { iterator, iterable, statements } => {
+                let iterable = Self::traverse_expression(iterable);
+                self.line(format!("for {} in {} {{", iterator, iterable).as_str());
+                self.traverse_block(statements);
+                self.line("};");
+            }
+            ast::StatementType::ReturnStatement { return_value } => {
+                let value = Self::traverse_expression(return_value);
+                self.line(format!("return {};", value).as_str());
+            }
+            ast::StatementType::InitializerStatement { variable_type, variable, default } => {
+                let mut s = Self::traverse_type(variable_type);
+                if !variable.is_empty() {
+                    s.push(' ');
+                    s.push_str(variable);
+                }
+                if let Some(expr) = default {
+                    s.push_str(" = ");
+                    s.push_str(Self::traverse_expression(expr).as_str());
+                }
+                s.push(';');
+                self.line(s.as_str());
+            }
+            ast::StatementType::AssignStatement { left, operator, right } => {
+                let op = match operator {
+                    ast::AssignOperator::Assign => "=",
+                };
+                let right = Self::traverse_expression(right);
+                self.line(format!("{} {} {};", left, op, right).as_str());
+            }
+            ast::StatementType::Expression { expression } => {
+                let expr = Self::traverse_expression(expression);
+                self.line(format!("{};", expr).as_str());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+    use crate::traverser::visitor::{LocationEraser, VisitorMut};
+
+    fn parse_without_locations(source: &str) -> ast::Program {
+        let mut program = parse_program(source).unwrap();
+        LocationEraser.visit_program_mut(&mut program);
+        program
+    }
+
+    fn assert_round_trip(source: &str) -> String {
+        let program = parse_without_locations(source);
+        let printed = FmtTraverser::traverse(&program);
+        assert_eq!(parse_without_locations(&printed), program, "{}", printed);
+        assert_eq!(FmtTraverser::traverse(&parse_program(&printed).unwrap()), printed);
+        printed
+    }
+
+    #[test]
+    fn test_canonical_layout() {
+        let printed = assert_round_trip(
+            "contract A{function f(private uint a,bytes4 b)returns uint{if a>1{uint c=a;bytes2 d;}else{a;};return a;}function g()returns bytes32{return \"x\";}} contract B{}",
+        );
+        assert_eq!(
+            printed,
+            "contract A {
+    function f(private uint a, bytes4 b) returns uint {
+        if a > 1 {
+            uint c = a;
+            bytes2 d;
+        } else {
+            a;
+        };
+        return a;
+    }
+
+    function g() returns bytes32 {
+        return \"x\";
+    }
+}
+
+contract B {
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_private_return() {
+        let printed = assert_round_trip("contract A { function f(private uint a) returns private uint { return a; } }");
+        assert!(printed.contains("function f(private uint a) returns private uint {"), "{}", printed);
+    }
+
+    #[test]
+    fn test_explicit_public_return() {
+        let program = parse_without_locations("contract A { function f(private uint a) returns public uint { return a; } }");
+        assert_eq!(program, parse_without_locations("contract A { function f(private uint a) returns uint { return a; } }"));
+        assert!(FmtTraverser::traverse(&program).contains("function f(private uint a) returns uint {"));
+    }
+
+    #[test]
+    fn test_array_types() {
+        let printed = assert_round_trip("contract A { function f(private bytes32[4] p, uint[2][3] q) returns bytes32[ 2 ] { return p; } }");
+        assert!(printed.contains("function f(private bytes32[4] p, uint[2][3] q) returns bytes32[2] {"), "{}", printed);
+    }
+
+    #[test]
+    fn test_minimal_parentheses() {
+        let printed = assert_round_trip(
+            "contract A { function f() returns uint { return ((a + b) * c) - (d - e) || (x && (y | z)) ^ w & (v == u) != (t < s) << 2 >> (1 + 1) % 3; } }",
+        );
+        assert!(printed.contains(
+            "return (a + b) * c - (d - e) || (x && y | z) ^ w & v == u != (t < s) << 2 >> (1 + 1) % 3;"
+        ), "{}", printed);
+    }
+
+    #[test]
+    fn test_literal_escapes() {
+        let printed = assert_round_trip(
+            "contract A { function f() returns bytes8 { return f('a\"b\\\\', \"\\x00\\n\\x7fé\"); } }",
+        );
+        assert!(printed.contains("return f(\"a\\\"b\\\\\", \"\\0\\n\\x7f\\xc3\\xa9\");"), "{}", printed);
+    }
+}
@@ -0,0 +1,131 @@
+use crate::ast;
+use crate::error::ZokError;
+use crate::traverser::visitor::walk_global_statement;
+use crate::traverser::{CircuitTraverser, Visitor};
+use crate::zkboo::cost::{GasModel, BLOCK_GAS_LIMIT};
+use crate::zkboo::optimizer::optimize;
+use crate::zkboo::zkboo::ZkBoo;
+
+type ZokResult<T> = Result<T, ZokError>;
+
+/// Reports what every function costs once compiled and optimized: its
+/// gates, the view words each party records per round, the size of a proof
+/// with `rounds` rounds and the gas the generated verifier needs for it.
+/// A function whose verifier would not fit in a block gets a warning.
+pub struct StatsTraverser {
+    zk_boo: ZkBoo,
+    gas: GasModel,
+    contract_name: String,
+    out: String,
+    // The first error; later functions are skipped.
+    error: Option<ZokError>,
+}
+
+impl StatsTraverser {
+    pub fn traverse(ast: &ast::Program, rounds: usize) -> ZokResult<String> {
+        let mut zelf = Self {
+            zk_boo: ZkBoo::new(rounds, 3, 2, 32),
+            gas: GasModel::default(),
+            contract_name: String::new(),
+            out: String::new(),
+            error: None,
+        };
+        zelf.visit_program(ast);
+        match zelf.error {
+            Some(err) => Err(err),
+            None => Ok(zelf.out),
+        }
+    }
+
+    fn traverse_function(&mut self, stmt: &ast::ContractStatement) -> ZokResult<()> {
+        match &stmt.node {
+            ast::ContractStatementType::FunctionStatement { function_name, .. } => {
+                let (circuit, report) = optimize(&CircuitTraverser::traverse(stmt)?);
+                let stats = report.after;
+                let cost = self.gas.estimate(&self.zk_boo, &circuit);
+                self.out.push_str(&format!("{}.{}\n", self.contract_name, function_name));
+                self.out.push_str(&format!(
+                    "  inputs: {} private, {} public, {} output words\n",
+                    stats.inputs, stats.public_inputs, stats.outputs,
+                ));
+                self.out.push_str(&format!(
+                    "  gates: {} and, {} add, {} xor, {} and_const, {} not, {} wiring\n",
+                    stats.and_gates, stats.add_gates, stats.xor_gates,
+                    stats.and_const_gates, stats.not_gates, stats.wiring_gates,
+                ));
+                self.out.push_str(&format!(
+                    "  view words per party per round: {} (before optimization: {})\n",
+                    stats.view_words(), report.before.view_words(),
+                ));
+                self.out.push_str(&format!("  and depth: {}\n", stats.and_depth));
+                self.out.push_str(&format!(
+                    "  proof size at {} rounds: {} bytes\n",
+                    self.zk_boo.num_of_round(), cost.proof_bytes,
+                ));
+                self.out.push_str(&format!("  verification gas: ~{}\n", cost.verify_gas));
+                if !cost.fits_in_block() {
+                    self.out.push_str(&format!(
+                        "  warning: over the block gas limit of {}, the verifier cannot run on chain\n",
+                        BLOCK_GAS_LIMIT,
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Only contracts and functions are visited; a function is costed as a
+// whole once compiled.
+impl Visitor for StatsTraverser {
+    fn visit_global_statement(&mut self, stmt: &ast::GlobalStatement) {
+        let ast::GlobalStatementType::ContractStatement { contract_name, .. } = &stmt.node;
+        self.contract_name = contract_name.clone();
+        walk_global_statement(self, stmt)
+    }
+
+    fn visit_contract_statement(&mut self, stmt: &ast::ContractStatement) {
+        if self.error.is_none() {
+            if let Err(err) = self.traverse_function(stmt) {
+                self.error = Some(err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+
+    #[test]
+    fn test_report_per_function() {
+        let program = parse_program(
+            "contract A { function f(private uint a, uint b) returns uint { return a & b; } function g(private uint a) returns uint { return a ^ 1; } }",
+        )
+        .unwrap();
+        let report = StatsTraverser::traverse(&program, 10).unwrap();
+        let lines: Vec<_> = report.lines().collect();
+        assert_eq!(lines[0], "A.f");
+        assert_eq!(lines[1], "  inputs: 1 private, 1 public, 1 output words");
+        assert_eq!(lines[2], "  gates: 1 and, 0 add, 0 xor, 0 and_const, 0 not, 0 wiring");
+        assert_eq!(lines[3], "  view words per party per round: 1 (before optimization: 1)");
+        assert_eq!(lines[5], "  proof size at 10 rounds: 1084 bytes");
+        assert_eq!(lines[7], "A.g");
+        assert_eq!(lines[9], "  gates: 0 and, 0 add, 1 xor, 0 and_const, 0 not, 0 wiring");
+        assert!(!report.contains("warning"), "{}", report);
+    }
+
+    #[test]
+    fn test_verifiers_over_the_block_gas_limit_are_flagged() {
+        let program = parse_program("contract A { function f(private bytes32 a) returns bytes32 { return sha256(a); } }").unwrap();
+        let report = StatsTraverser::traverse(&program, 137).unwrap();
+        assert_eq!(
+            report.lines().last(),
+            Some("  warning: over the block gas limit of 30000000, the verifier cannot run on chain"),
+        );
+        // A single round is still just under it.
+        let report = StatsTraverser::traverse(&program, 1).unwrap();
+        assert!(!report.contains("warning"), "{}", report);
+    }
+}
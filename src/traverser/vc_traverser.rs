@@ -1,236 +1,319 @@
-use std::collections::HashMap;
 use crate::ast;
-use crate::ast::{ContractStatement, Parameter};
+use crate::ast::ContractStatement;
 use crate::error::{ZokError, ZokErrorType};
-use std::fs;
+use crate::zkboo::circuit::{Circuit, Gate};
+use crate::zkboo::optimizer::{optimize, OptimizeReport};
+use crate::zkboo::transcript::Transcript;
+use crate::traverser::{CircuitTraverser, FmtTraverser, COMMITMENT_WORDS};
 
 type ZokResult<T> = Result<T, ZokError>;
 
+// Built into the binary, so it runs from any directory.
+const TEMPLATE: &str = include_str!("zkboo.sol");
+// The vectors the Rust tests check; `vectors` checks the template on them.
+const TAPE_VECTORS: &str = include_str!("../zkboo/random_tape_vectors.txt");
+const RANGE_VECTORS: &str = include_str!("../zkboo/circuit/range_vectors.txt");
+
+/// Emits the Solidity verifier of a contract. `rounds` must match the
+/// rounds the proofs are made with.
 pub struct VCTraverser {
-    input: HashMap<String, usize>,
-    in_pub: HashMap<String, usize>,
-    input_index: usize,
-    in_pub_index: usize,
+    rounds: usize,
 }
 
 impl VCTraverser {
-    fn new() -> Self {
-        Self {
-            input: Default::default(),
-            in_pub: Default::default(),
-            input_index: 0,
-            in_pub_index: 0,
-        }
+    fn new(rounds: usize) -> Self {
+        Self { rounds }
     }
 
-    pub fn traverse(ast: ast::Program) -> ZokResult<String> {
-        match ast {
-            ast::Program::GlobalStatements(v) => {
-                for gs in v {
-                    let mut zelf = Self::new();
-                    let stmt = zelf.traverse_global_statement(&gs);
-                    return match stmt {
-                        Ok(res) => {
-                            Ok(res)
-                        }
-                        Err(err) => {
-                            Err(err)
-                        }
-                    };
-                }
-                Err(ZokError {
-                    error: ZokErrorType::EOF,
-                    location: Default::default(),
-                })
-            }
+    // The template is a single contract, so only one can be emitted.
+    pub fn traverse(ast: ast::Program, rounds: usize) -> ZokResult<String> {
+        let ast::Program::GlobalStatements(v) = ast;
+        match v.as_slice() {
+            [gs] => Self::new(rounds).traverse_global_statement(gs),
+            [_, extra, ..] => Err(ZokError {
+                error: ZokErrorType::UnsupportedError,
+                location: extra.location,
+            }),
+            [] => Err(ZokError {
+                error: ZokErrorType::EOF,
+                location: Default::default(),
+            }),
         }
     }
 
     fn traverse_global_statement(&mut self, stmt: &ast::GlobalStatement) -> ZokResult<String> {
         match &stmt.node {
             ast::GlobalStatementType::ContractStatement { contract_name, members } => {
-                let contents = match fs::read_to_string("src/traverser/zkboo.sol") {
-                    Ok(content) => Ok(content),
-                    Err(err) => Err(ZokError {
-                        error: ZokErrorType::UnsupportedError,
-                        location: Default::default(),
-                    }),
-                };
-                let mut contents = contents?;
                 let mut s = String::new();
-                let mut funcs = vec![];
                 for member in members {
-                    let (mem, func) = self.traverse_member(member)?;
-                    s.push_str(mem.as_str());
-                    funcs.push(func);
+                    s.push_str(self.traverse_member(contract_name, member)?.as_str());
                 }
-                for func in funcs {
-                    contents = contents.replace("{circuit}", func.as_str());
-                }
-                Ok(contents.replace("{contract_name}", contract_name)
-                    .replace("{functions}", s.as_str()))
+                Ok(self.fill_template(contract_name, &s))
+            }
+        }
+    }
+
+    fn fill_template(&self, contract_name: &str, functions: &str) -> String {
+        TEMPLATE.replace("{contract_name}", contract_name)
+            .replace("{rounds}", self.rounds.to_string().as_str())
+            .replace("{functions}", functions)
+    }
+
+    /// A contract that runs the template's own code on the test vectors of
+    /// the Rust prover: `check_random_tape()` draws the words of every tape
+    /// in `random_tape_vectors.txt` with `get_next_random_from_context`,
+    /// and `check_in_range()` evaluates the emitted `in_range` gates on
+    /// every line of `range_vectors.txt`. Both revert on the first vector
+    /// that differs and return true otherwise.
+    pub fn vectors(rounds: usize) -> String {
+        let mut s = String::new();
+        s.push_str("    function tape(bytes memory seed) internal pure returns (IKosContext memory) {\n");
+        s.push_str("        IKosView memory ikos_view;\n");
+        s.push_str("        ikos_view.rand_tape_seed = seed;\n");
+        s.push_str("        return new_views(ikos_view);\n");
+        s.push_str("    }\n\n");
+        s.push_str("    function check_random_tape() public pure returns (bool) {\n");
+        s.push_str("        IKosContext memory ctx;\n");
+        for line in TAPE_VECTORS.lines().filter(|line| !line.starts_with('#')) {
+            let mut fields = line.split_whitespace();
+            let seed = fields.next().unwrap_or_default();
+            s.push_str(format!("        ctx = tape(hex\"{}\");\n", seed).as_str());
+            for (i, word) in fields.enumerate() {
+                s.push_str(format!("        require(get_next_random_from_context(ctx) == 0x{}, \"tape {} word {}\");\n", word, seed, i).as_str());
+            }
+        }
+        s.push_str("        return true;\n");
+        s.push_str("    }\n\n");
+
+        // With every operand public the gates run on plain words, so no
+        // proof is needed to evaluate them.
+        s.push_str("    function _in_range(IKosVariable4V[] memory input, uint32[] memory in_pub) internal pure returns (IKosVariable4V[] memory) {\n");
+        s.push_str(Self::traverse_circuit(&Self::in_range_circuit()).as_str());
+        s.push_str("    }\n\n");
+        s.push_str("    function in_range(uint32 x, uint32 lo, uint32 hi) internal pure returns (uint32) {\n");
+        s.push_str("        uint32[] memory in_pub = new uint32[](3);\n");
+        s.push_str("        in_pub[0] = x;\n");
+        s.push_str("        in_pub[1] = lo;\n");
+        s.push_str("        in_pub[2] = hi;\n");
+        s.push_str("        return _in_range(new IKosVariable4V[](0), in_pub)[0].value[0];\n");
+        s.push_str("    }\n\n");
+        s.push_str("    function check_in_range() public pure returns (bool) {\n");
+        for line in RANGE_VECTORS.lines().filter(|line| !line.starts_with('#')) {
+            let fields: Vec<_> = line.split_whitespace().collect();
+            if let [x, lo, hi, expected] = fields.as_slice() {
+                let expected = if *expected == "1" { u32::MAX } else { 0 };
+                s.push_str(format!("        require(in_range(0x{}, 0x{}, 0x{}) == 0x{:08x}, \"in_range {}\");\n", x, lo, hi, expected, line).as_str());
             }
         }
+        s.push_str("        return true;\n");
+        s.push_str("    }\n");
+        Self::new(rounds).fill_template("ZokerVectors", &s)
     }
 
-    fn traverse_member(&mut self, stmt: &ContractStatement) -> ZokResult<(String, String)> {
+    // `in_range(x, lo, hi)` of three public words, optimized like every
+    // emitted circuit.
+    fn in_range_circuit() -> Circuit {
+        let mut circuit = Circuit::new();
+        let x = circuit.public_input();
+        let lo = circuit.public_input();
+        let hi = circuit.public_input();
+        let inside = circuit.in_range(x, lo, hi);
+        circuit.set_outputs(vec![inside]);
+        optimize(&circuit).0
+    }
+
+    fn traverse_member(&mut self, contract_name: &str, stmt: &ContractStatement) -> ZokResult<String> {
+        let digest = Transcript::circuit_digest(&FmtTraverser::traverse_function(contract_name, stmt));
+        let digest: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
         match &stmt.node {
-            ast::ContractStatementType::FunctionStatement { function_name, parameters, return_type: _return_type, statements } => {
+            ast::ContractStatementType::FunctionStatement { function_name, return_type, private_return, .. } => {
+                let (circuit, report) = optimize(&CircuitTraverser::traverse(stmt)?);
                 let mut s = String::new();
                 s.push_str("    function _");
                 s.push_str(function_name.as_str());
                 s.push_str("(IKosVariable4V[] memory input, uint32[] memory in_pub) internal pure returns (IKosVariable4V[] memory) {\n");
-                for parameter in parameters {
-                    self.traverse_parameter(parameter);
-                }
-                for statement in statements {
-                    let stmt = self.traverse_statement(statement);
-                    s.push_str(stmt.as_str());
-                }
+                s.push_str(Self::traverse_report(&report).as_str());
+                s.push_str(Self::traverse_circuit(&circuit).as_str());
                 s.push_str("    }\n\n");
 
+                // Words past the circuit's outputs would be hashed into the
+                // challenge and returned as they are, so the length is fixed.
+                // A proof whose error word is set shows that a check failed.
+                let mut valid = format!("proof.output.length == {}", circuit.outputs().len());
+                if circuit.error().is_some() {
+                    valid.push_str(format!(" && proof.output[{}] == 0", circuit.outputs().len() - 1).as_str());
+                }
+                valid.push_str(format!(" && ZKBoo_verify(proof, 0x{}, _{})", digest, function_name).as_str());
                 s.push_str("    function ");
                 s.push_str(function_name.as_str());
-                s.push_str("(VerifyingProof memory proof) public pure returns (uint256) {\n");
-                s.push_str("        if (ZKBoo_verify(proof)) { return proof.output[0]; } else { return 0; }\n    ");
+                if *private_return {
+                    // The commitment's words packed into a `bytes32`.
+                    s.push_str("(VerifyingProof memory proof) public pure returns (bytes32) {\n");
+                    s.push_str(format!("        if (!({})) {{ return bytes32(0); }}\n", valid).as_str());
+                    s.push_str("        uint256 commitment = 0;\n");
+                    s.push_str(format!("        for (uint i = 0; i < {}; i++) {{ commitment = commitment << 32 | proof.output[i]; }}\n", COMMITMENT_WORDS).as_str());
+                    s.push_str("        return bytes32(commitment);\n    ");
+                } else if let ast::Type::UInt256 = return_type {
+                    s.push_str("(VerifyingProof memory proof) public pure returns (uint256) {\n");
+                    s.push_str(format!("        if ({}) {{ return proof.output[0]; }} else {{ return 0; }}\n    ", valid).as_str());
+                } else {
+                    s.push_str("(VerifyingProof memory proof) public pure returns (uint32[] memory) {\n");
+                    s.push_str(format!("        if ({}) {{ return proof.output; }} else {{ return new uint32[](0); }}\n    ", valid).as_str());
+                }
                 s.push_str("}\n");
-                Ok((s, function_name.clone()))
+                Ok(s)
             }
         }
     }
 
-    // TODO
-    fn traverse_parameter(&mut self, param: &Parameter) {
-        match &param.node {
-            ast::ParameterType::Private { variable_type: _variable_type, variable }
-            => {
-                self.input.insert(variable.to_string(), self.input_index);
-                self.input_index += 1;
-            } // TODO: 이후 타입도 같이 넘겨야 함
-            ast::ParameterType::Public { variable_type: _variable_type, variable }
-            => {
-                self.in_pub.insert(variable.to_string(), self.in_pub_index);
-                self.in_pub_index += 1;
-            }
-        }
+    fn traverse_report(report: &OptimizeReport) -> String {
+        let (before, after) = (&report.before, &report.after);
+        format!(
+            "        // view words: {} -> {}, local gates: {} -> {}\n",
+            before.view_words(),
+            after.view_words(),
+            before.local_gates(),
+            after.local_gates(),
+        )
     }
 
-    // TODO
-    fn traverse_statement(&mut self, stmt: &ast::Statement) -> String {
+    // One `w[i]` per gate; a memory array rather than locals keeps large
+    // circuits clear of the stack limit.
+    fn traverse_circuit(circuit: &Circuit) -> String {
         let mut s = String::new();
-        match &stmt.node {
-            ast::StatementType::IfStatement { condition, if_statements, else_statements } => {
-                // Self::traverse_expression(&mut ctx.sub_context(), condition);
-                // TODO: condition을 적용할 연산을 정의해야함 (assign에서 11111 을 & 하고 더할 값)
-            }
-            ast::StatementType::ForEachStatement { iterator, iterable, statements } => {
-                // TODO: foreach는 vector를 type 추가한 후에 구현 가능
-                // let var = Self::traverse_expression(iterable);
-                // TODO: iterator를 받아서 처리
-                // let ops = statements.iter().fold(ctx.sub_context(), Self::traverse_statement).ops();
-                // ctx.with_op(repeat()) // TODO: iterable을 받아서 처리
-                //     .with_op(push(Functor::new(ops)))
-            }
-            ast::StatementType::ReturnStatement { return_value } => {
-                s.push_str("        IKosVariable4V[] memory out = new IKosVariable4V[](1);\n");
-                s.push_str("        out[0] = ");
-                s.push_str(self.traverse_expression(return_value).as_str());
-                s.push_str(";\n");
-                s.push_str("        return out;\n");
-            }
-            ast::StatementType::InitializerStatement { variable_type: _variable_type, variable, default } => {
-                s.push_str("        ");
-                s.push_str("IKosVariable4V ");
-                s.push_str(variable.as_str());
-                match default {
-                    Some(expr) => {
-                        s.push_str(" = ");
-                        s.push_str(self.traverse_expression(expr).as_str());
-                    }
-                    None => {}
-                };
-                s.push_str(";\n");
-            }
-            ast::StatementType::AssignStatement { left, operator: _, right } => {
-                s.push_str("        ");
-                s.push_str(left.as_str());
-                s.push_str(" = ");
-                s.push_str(self.traverse_expression(right).as_str());
-                s.push_str(";\n");
-            }
-            ast::StatementType::Expression { expression } => {}
-        };
+        s.push_str(format!("        IKosVariable4V[] memory w = new IKosVariable4V[]({});\n", circuit.gates().len()).as_str());
+        for (i, gate) in circuit.gates().iter().enumerate() {
+            let value = match *gate {
+                Gate::Input(j) => format!("input[{}]", j),
+                Gate::PublicInput(j) => format!("IKosVariable_new_value(in_pub[{}])", j),
+                Gate::Const(value) => format!("IKosVariable_new_value(0x{:08x})", value),
+                Gate::Xor(a, b) => format!("bit_xor(w[{}], w[{}])", a, b),
+                Gate::And(a, b) => format!("bit_and(w[{}], w[{}])", a, b),
+                Gate::AndConst(a, mask) => format!("and_const(w[{}], 0x{:08x})", a, mask),
+                Gate::Not(a) => format!("negate(w[{}])", a),
+                Gate::RotateRight(a, n) => format!("rotr(w[{}], {})", a, n),
+                Gate::ShiftRight(a, n) => format!("rshift(w[{}], {})", a, n),
+                Gate::ShiftLeft(a, n) => format!("lshift(w[{}], {})", a, n),
+                Gate::Add(a, b) => format!("add_op(w[{}], w[{}])", a, b),
+            };
+            s.push_str(format!("        w[{}] = {};\n", i, value).as_str());
+        }
+        s.push_str(format!("        IKosVariable4V[] memory out = new IKosVariable4V[]({});\n", circuit.outputs().len()).as_str());
+        for (i, wire) in circuit.outputs().iter().enumerate() {
+            s.push_str(format!("        out[{}] = w[{}];\n", i, wire).as_str());
+        }
+        s.push_str("        return out;\n");
         s
     }
+}
 
-    // TODO
-    fn traverse_expression(&mut self, expr: &ast::Expression) -> String {
-        let mut s = String::new();
-        match &expr.node {
-            ast::ExpressionType::BinaryExpression {
-                left, operator, right
-            } => {
-                let v1 = self.traverse_expression(left);
-                let v2 = self.traverse_expression(right);
-                let op = Self::traverse_operator(operator);
-                s.push_str(op);
-                s.push_str("(");
-                s.push_str(v1.as_str());
-                s.push_str(",");
-                s.push_str(v2.as_str());
-                s.push_str(")");
-            }
-            ast::ExpressionType::FunctionCallExpression { function_name, arguments: _arguments } => {
-                // TODO:
-                s.push_str("_");
-                s.push_str(function_name.as_str());
-                s.push_str("(");
-                s.push_str("proof");
-                s.push_str(")");
-            }
-            ast::ExpressionType::Number { value } => {
-                s.push_str("IKosVariable_new_value(");
-                s.push_str(format!("{}", value).as_str());
-                s.push_str(")");
-            }
-            ast::ExpressionType::Identifier { value } => {
-                if self.input.contains_key(value) {
-                    s.push_str("input[");
-                    s.push_str(format!("{}", self.input.get(value).unwrap()).as_str());
-                    s.push_str("]");
-                } else if self.in_pub.contains_key(value) {
-                    s.push_str("in_pub[");
-                    s.push_str(format!("{}", self.in_pub.get(value).unwrap()).as_str());
-                    s.push_str("]");
-                }
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+    use crate::zkboo::zkboo::Circuit4VTrait;
+
+    #[test]
+    fn test_every_entry_point_verifies_its_own_circuit() {
+        let source = "contract A { function f(private uint a) returns uint { return a + 1; } function g(private uint a) returns uint { return a ^ 1; } }";
+        let program = parse_program(source).unwrap();
+        let contract = VCTraverser::traverse(program.clone(), 2).unwrap();
+        assert!(!contract.contains("{circuit}"));
+
+        let ast::Program::GlobalStatements(statements) = &program;
+        let ast::GlobalStatementType::ContractStatement { members, .. } = &statements[0].node;
+        let mut calls = vec![];
+        for (member, name) in members.iter().zip(["f", "g"].iter()) {
+            let digest = Transcript::circuit_digest(&FmtTraverser::traverse_function("A", member));
+            let digest: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+            let call = format!("ZKBoo_verify(proof, 0x{}, _{})", digest, name);
+            assert_eq!(contract.matches(call.as_str()).count(), 1, "{}", call);
+            calls.push(call);
         }
-        s
+        assert_ne!(calls[0], calls[1]);
+        assert_eq!(contract.matches("ZKBoo_verify(proof, ").count(), 2);
+    }
+
+    // The body of `function name(` in the emitted contract.
+    fn body<'a>(contract: &'a str, name: &str) -> &'a str {
+        let start = contract.find(format!("function {}(", name).as_str()).unwrap();
+        let len = contract[start..].find("\n    }\n").unwrap();
+        &contract[start..start + len]
     }
 
-    // TODO
-    fn traverse_operator(op: &ast::BinaryOperator) -> &str {
-        match op {
-            ast::BinaryOperator::Add => "add_op",
-            // ast::BinaryOperator::Sub => "",
-            // ast::BinaryOperator::Mul => ctx.bin_op(lazy_mul),
-            // ast::BinaryOperator::Div => ctx.bin_op(lazy_div),
-            // TODO: 나중에 구현
-            // BinaryOperator::Mod => ctx.with_op(bin_op(add)),
-            // BinaryOperator::And => ctx.with_op(bin_op(add)),
-            // ast::BinaryOperator::Or => "bit_or",
-            // ast::BinaryOperator::Lt => ctx.with_op(bin_op(add)),
-            // ast::BinaryOperator::Le => ctx.with_op(bin_op(add)),
-            ast::BinaryOperator::Gt => "gt_op",
-            // BinaryOperator::Ge => ctx.with_op(bin_op(add)),
-            // BinaryOperator::Eq => ctx.with_op(bin_op(add)),
-            // BinaryOperator::NotEq => ctx.with_op(bin_op(add)),
-            ast::BinaryOperator::BitAnd => "bit_and",
-            ast::BinaryOperator::BitOr => "bit_or",
-            ast::BinaryOperator::BitXor => "bit_xor",
-            ast::BinaryOperator::LShift => "rshift",
-            ast::BinaryOperator::RShift => "lshift",
-            _ => "",
+    #[test]
+    fn test_xor_of_two_public_inputs_does_not_recurse() {
+        let program = parse_program("contract A { function f(uint p, uint q) returns uint { return p ^ q; } }").unwrap();
+        let contract = VCTraverser::traverse(program, 2).unwrap();
+        let circuit = body(&contract, "_f");
+        assert!(circuit.contains("w[0] = IKosVariable_new_value(in_pub[0]);"), "{}", circuit);
+        assert!(circuit.contains("w[1] = IKosVariable_new_value(in_pub[1]);"), "{}", circuit);
+        assert!(circuit.contains("= bit_xor(w[0], w[1]);"), "{}", circuit);
+
+        // Each gate that swaps a public `self` with `rhs` handles two public
+        // operands first, or it would swap them forever.
+        for gate in ["bit_xor", "bit_and", "add_op"].iter() {
+            let gate_body = body(&contract, gate);
+            let both = gate_body.find("if (is_empty_context(self) && is_empty_context(rhs)) {").unwrap();
+            let swap = gate_body.find(format!("return {}(rhs, self);", gate).as_str()).unwrap();
+            assert!(both < swap, "{}", gate_body);
+        }
+    }
+
+    #[test]
+    fn test_every_entry_point_checks_the_output_length() {
+        let source = "contract A { function f(private uint a) returns uint { return a; } function g(private bytes8 a) returns bytes8 { return a; } function h(private uint a) returns private uint { return a; } }";
+        let contract = VCTraverser::traverse(parse_program(source).unwrap(), 2).unwrap();
+        for (name, words) in [("f", 1), ("g", 2), ("h", COMMITMENT_WORDS)].iter() {
+            let entry_point = body(&contract, name);
+            let check = format!("proof.output.length == {} && ZKBoo_verify(", words);
+            assert!(entry_point.contains(check.as_str()), "{}", entry_point);
+        }
+    }
+
+    #[test]
+    fn test_only_one_contract_is_emitted() {
+        let program = parse_program("contract A { function f(private uint a) returns uint { return a; } } contract B { }").unwrap();
+        let err = VCTraverser::traverse(program, 2).unwrap_err();
+        assert_eq!(err.error, ZokErrorType::UnsupportedError);
+        assert_eq!(err.location.column(), 70);
+    }
+
+    #[test]
+    fn test_every_tape_vector_is_checked() {
+        let contract = VCTraverser::vectors(2);
+        let checks = body(&contract, "check_random_tape");
+        assert!(checks.contains("ctx = tape(hex\"000102030405060708090a0b0c0d0e0f\");\n        require(get_next_random_from_context(ctx) == 0x855d3b82, "), "{}", checks);
+        // Word 8 is the first of block 1.
+        assert!(checks.contains("require(get_next_random_from_context(ctx) == 0xe9ff0e6e, \"tape 00000000000000000000000000000000 word 8\");"), "{}", checks);
+        assert_eq!(checks.matches("ctx = tape(").count(), 3);
+        assert_eq!(checks.matches("require(").count(), 3 * 12);
+    }
+
+    #[test]
+    fn test_every_range_vector_is_checked() {
+        let contract = VCTraverser::vectors(2);
+        let checks = body(&contract, "check_in_range");
+        assert!(checks.contains("require(in_range(0x13, 0x13, 0x41) == 0xffffffff, \"in_range 13 13 41 1\");"), "{}", checks);
+        assert!(checks.contains("require(in_range(0x20, 0x30, 0x10) == 0x00000000, \"in_range 20 30 10 0\");"), "{}", checks);
+        let lines = RANGE_VECTORS.lines().filter(|line| !line.starts_with('#')).count();
+        assert_eq!(checks.matches("require(").count(), lines);
+        assert!(body(&contract, "_in_range").contains("bit_and("));
+
+        // The same gates on public words, as the contract evaluates them.
+        let circuit = VCTraverser::in_range_circuit();
+        for line in RANGE_VECTORS.lines().filter(|line| !line.starts_with('#')) {
+            let fields: Vec<_> = line.split_whitespace().map(|f| u32::from_str_radix(f, 16).unwrap()).collect();
+            let out = Circuit4VTrait::circuit(&circuit, &[], &fields[..3]).unwrap();
+            assert_eq!(out[0].value, vec![fields[3] * u32::MAX; 2], "{}", line);
         }
     }
+
+    #[test]
+    fn test_rounds_are_emitted() {
+        let program = parse_program("contract A { function f(private uint a) returns uint { return a; } }").unwrap();
+        let contract = VCTraverser::traverse(program, 137).unwrap();
+        assert!(contract.contains("uint32 constant OZKB_NUMBER_OF_ROUNDS = 137;"));
+        assert!(!contract.contains("{rounds}"));
+    }
 }
@@ -0,0 +1,376 @@
+use crate::ast;
+use crate::location::Location;
+
+/// Read-only traversal over an `ast::Program`.
+///
+/// Every `visit_*` method defaults to the matching `walk_*` function, which
+/// visits all children of the node. Override a method to act on a node and
+/// call the `walk_*` function from it to keep descending.
+pub trait Visitor {
+    fn visit_program(&mut self, program: &ast::Program) {
+        walk_program(self, program)
+    }
+
+    fn visit_global_statement(&mut self, stmt: &ast::GlobalStatement) {
+        walk_global_statement(self, stmt)
+    }
+
+    fn visit_contract_statement(&mut self, stmt: &ast::ContractStatement) {
+        walk_contract_statement(self, stmt)
+    }
+
+    fn visit_parameter(&mut self, param: &ast::Parameter) {
+        walk_parameter(self, param)
+    }
+
+    fn visit_statement(&mut self, stmt: &ast::Statement) {
+        walk_statement(self, stmt)
+    }
+
+    fn visit_expression(&mut self, expr: &ast::Expression) {
+        walk_expression(self, expr)
+    }
+
+    fn visit_type(&mut self, _typ: &ast::Type) {}
+}
+
+/// Mutable traversal over an `ast::Program`, for passes that rewrite nodes
+/// in place. The `walk_*_mut` functions descend like their read-only
+/// counterparts.
+pub trait VisitorMut {
+    fn visit_program_mut(&mut self, program: &mut ast::Program) {
+        walk_program_mut(self, program)
+    }
+
+    fn visit_global_statement_mut(&mut self, stmt: &mut ast::GlobalStatement) {
+        walk_global_statement_mut(self, stmt)
+    }
+
+    fn visit_contract_statement_mut(&mut self, stmt: &mut ast::ContractStatement) {
+        walk_contract_statement_mut(self, stmt)
+    }
+
+    fn visit_parameter_mut(&mut self, param: &mut ast::Parameter) {
+        walk_parameter_mut(self, param)
+    }
+
+    fn visit_statement_mut(&mut self, stmt: &mut ast::Statement) {
+        walk_statement_mut(self, stmt)
+    }
+
+    fn visit_expression_mut(&mut self, expr: &mut ast::Expression) {
+        walk_expression_mut(self, expr)
+    }
+
+    fn visit_type_mut(&mut self, _typ: &mut ast::Type) {}
+}
+
+pub fn walk_program<V: Visitor + ?Sized>(visitor: &mut V, program: &ast::Program) {
+    match program {
+        ast::Program::GlobalStatements(stmts) => {
+            for stmt in stmts {
+                visitor.visit_global_statement(stmt);
+            }
+        }
+    }
+}
+
+pub fn walk_global_statement<V: Visitor + ?Sized>(visitor: &mut V, stmt: &ast::GlobalStatement) {
+    match &stmt.node {
+        ast::GlobalStatementType::ContractStatement { contract_name: _, members } => {
+            for member in members {
+                visitor.visit_contract_statement(member);
+            }
+        }
+    }
+}
+
+pub fn walk_contract_statement<V: Visitor + ?Sized>(visitor: &mut V, stmt: &ast::ContractStatement) {
+    match &stmt.node {
+        ast::ContractStatementType::FunctionStatement { function_name: _, parameters, return_type, private_return: _, statements } => {
+            for param in parameters {
+                visitor.visit_parameter(param);
+            }
+            visitor.visit_type(return_type);
+            for statement in statements {
+                visitor.visit_statement(statement);
+            }
+        }
+    }
+}
+
+pub fn walk_parameter<V: Visitor + ?Sized>(visitor: &mut V, param: &ast::Parameter) {
+    match &param.node {
+        ast::ParameterType::Private { variable_type, variable: _ }
+        | ast::ParameterType::Public { variable_type, variable: _ } => {
+            visitor.visit_type(variable_type);
+        }
+    }
+}
+
+pub fn walk_statement<V: Visitor + ?Sized>(visitor: &mut V, stmt: &ast::Statement) {
+    match &stmt.node {
+        ast::StatementType::IfStatement { condition, if_statements, else_statements } => {
+            visitor.visit_expression(condition);
+            for statement in if_statements {
+                visitor.visit_statement(statement);
+            }
+            for statement in else_statements {
+                visitor.visit_statement(statement);
+            }
+        }
+        ast::StatementType::ForEachStatement { iterator: _, iterable, statements } => {
+            visitor.visit_expression(iterable);
+            for statement in statements {
+                visitor.visit_statement(statement);
+            }
+        }
+        ast::StatementType::ReturnStatement { return_value } => {
+            visitor.visit_expression(return_value);
+        }
+        ast::StatementType::InitializerStatement { variable_type, variable: _, default } => {
+            visitor.visit_type(variable_type);
+            if let Some(expr) = default {
+                visitor.visit_expression(expr);
+            }
+        }
+        ast::StatementType::AssignStatement { left: _, operator: _, right } => {
+            visitor.visit_expression(right);
+        }
+        ast::StatementType::Expression { expression } => {
+            visitor.visit_expression(expression);
+        }
+    }
+}
+
+pub fn walk_expression<V: Visitor + ?Sized>(visitor: &mut V, expr: &ast::Expression) {
+    match &expr.node {
+        ast::ExpressionType::BinaryExpression { left, operator: _, right } => {
+            visitor.visit_expression(left);
+            visitor.visit_expression(right);
+        }
+        ast::ExpressionType::FunctionCallExpression { function_name: _, arguments } => {
+            for argument in arguments {
+                visitor.visit_expression(argument);
+            }
+        }
+        ast::ExpressionType::Number { .. }
+        | ast::ExpressionType::Identifier { .. }
+        | ast::ExpressionType::Literal { .. } => {}
+    }
+}
+
+pub fn walk_program_mut<V: VisitorMut + ?Sized>(visitor: &mut V, program: &mut ast::Program) {
+    match program {
+        ast::Program::GlobalStatements(stmts) => {
+            for stmt in stmts {
+                visitor.visit_global_statement_mut(stmt);
+            }
+        }
+    }
+}
+
+pub fn walk_global_statement_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut ast::GlobalStatement) {
+    match &mut stmt.node {
+        ast::GlobalStatementType::ContractStatement { contract_name: _, members } => {
+            for member in members {
+                visitor.visit_contract_statement_mut(member);
+            }
+        }
+    }
+}
+
+pub fn walk_contract_statement_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut ast::ContractStatement) {
+    match &mut stmt.node {
+        ast::ContractStatementType::FunctionStatement { function_name: _, parameters, return_type, private_return: _, statements } => {
+            for param in parameters {
+                visitor.visit_parameter_mut(param);
+            }
+            visitor.visit_type_mut(return_type);
+            for statement in statements {
+                visitor.visit_statement_mut(statement);
+            }
+        }
+    }
+}
+
+pub fn walk_parameter_mut<V: VisitorMut + ?Sized>(visitor: &mut V, param: &mut ast::Parameter) {
+    match &mut param.node {
+        ast::ParameterType::Private { variable_type, variable: _ }
+        | ast::ParameterType::Public { variable_type, variable: _ } => {
+            visitor.visit_type_mut(variable_type);
+        }
+    }
+}
+
+pub fn walk_statement_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut ast::Statement) {
+    match &mut stmt.node {
+        ast::StatementType::IfStatement { condition, if_statements, else_statements } => {
+            visitor.visit_expression_mut(condition);
+            for statement in if_statements {
+                visitor.visit_statement_mut(statement);
+            }
+            for statement in else_statements {
+                visitor.visit_statement_mut(statement);
+            }
+        }
+        ast::StatementType::ForEachStatement { iterator: _, iterable, statements } => {
+            visitor.visit_expression_mut(iterable);
+            for statement in statements {
+                visitor.visit_statement_mut(statement);
+            }
+        }
+        ast::StatementType::ReturnStatement { return_value } => {
+            visitor.visit_expression_mut(return_value);
+        }
+        ast::StatementType::InitializerStatement { variable_type, variable: _, default } => {
+            visitor.visit_type_mut(variable_type);
+            if let Some(expr) = default {
+                visitor.visit_expression_mut(expr);
+            }
+        }
+        ast::StatementType::AssignStatement { left: _, operator: _, right } => {
+            visitor.visit_expression_mut(right);
+        }
+        ast::StatementType::Expression { expression } => {
+            visitor.visit_expression_mut(expression);
+        }
+    }
+}
+
+pub fn walk_expression_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut ast::Expression) {
+    match &mut expr.node {
+        ast::ExpressionType::BinaryExpression { left, operator: _, right } => {
+            visitor.visit_expression_mut(left);
+            visitor.visit_expression_mut(right);
+        }
+        ast::ExpressionType::FunctionCallExpression { function_name: _, arguments } => {
+            for argument in arguments {
+                visitor.visit_expression_mut(argument);
+            }
+        }
+        ast::ExpressionType::Number { .. }
+        | ast::ExpressionType::Identifier { .. }
+        | ast::ExpressionType::Literal { .. } => {}
+    }
+}
+
+/// Resets every location so trees parsed from differently laid out
+/// sources can be compared.
+pub struct LocationEraser;
+
+impl VisitorMut for LocationEraser {
+    fn visit_global_statement_mut(&mut self, stmt: &mut ast::GlobalStatement) {
+        stmt.location = Location::default();
+        walk_global_statement_mut(self, stmt)
+    }
+
+    fn visit_contract_statement_mut(&mut self, stmt: &mut ast::ContractStatement) {
+        stmt.location = Location::default();
+        walk_contract_statement_mut(self, stmt)
+    }
+
+    fn visit_parameter_mut(&mut self, param: &mut ast::Parameter) {
+        param.location = Location::default();
+        walk_parameter_mut(self, param)
+    }
+
+    fn visit_statement_mut(&mut self, stmt: &mut ast::Statement) {
+        stmt.location = Location::default();
+        walk_statement_mut(self, stmt)
+    }
+
+    fn visit_expression_mut(&mut self, expr: &mut ast::Expression) {
+        expr.location = Location::default();
+        walk_expression_mut(self, expr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+    use crate::test_support::{binary, located};
+
+    struct IdentifierCollector {
+        names: Vec<String>,
+    }
+
+    impl Visitor for IdentifierCollector {
+        fn visit_expression(&mut self, expr: &ast::Expression) {
+            if let Some(name) = expr.node.identifier_name() {
+                self.names.push(name);
+            }
+            walk_expression(self, expr)
+        }
+    }
+
+    struct NumberDoubler;
+
+    impl VisitorMut for NumberDoubler {
+        fn visit_expression_mut(&mut self, expr: &mut ast::Expression) {
+            if let ast::ExpressionType::Number { value } = &mut expr.node {
+                *value *= 2;
+            }
+            walk_expression_mut(self, expr)
+        }
+    }
+
+    const SOURCE: &str = "contract A { function f(private uint a, uint b) returns uint { if a > 1 { uint c = b + 2; }; return (a + b) & f(a, 3); } }";
+
+    #[test]
+    fn test_visitor_reaches_nested_expressions() {
+        let program = parse_program(SOURCE).unwrap();
+        let mut collector = IdentifierCollector { names: vec![] };
+        collector.visit_program(&program);
+        assert_eq!(collector.names, vec!["a", "b", "a", "b", "a"]);
+    }
+
+    fn number(value: u64) -> ast::Expression {
+        located(ast::ExpressionType::Number { value })
+    }
+
+    fn identifier(value: &str) -> ast::Expression {
+        located(ast::ExpressionType::Identifier { value: value.to_string() })
+    }
+
+    // `contract A { function f(private uint a) returns uint { if a > x { uint c = a + y; }; return f(a, z); } }`
+    fn program([x, y, z]: [u64; 3]) -> ast::Program {
+        let if_statement = ast::StatementType::IfStatement {
+            condition: binary(identifier("a"), &ast::BinaryOperator::Gt, number(x)),
+            if_statements: vec![located(ast::StatementType::InitializerStatement {
+                variable_type: ast::Type::UInt256,
+                variable: "c".to_string(),
+                default: Some(binary(identifier("a"), &ast::BinaryOperator::Add, number(y))),
+            })],
+            else_statements: vec![],
+        };
+        let return_statement = ast::StatementType::ReturnStatement {
+            return_value: located(ast::ExpressionType::FunctionCallExpression {
+                function_name: "f".to_string(),
+                arguments: vec![identifier("a"), number(z)],
+            }),
+        };
+        let function = ast::ContractStatementType::FunctionStatement {
+            function_name: "f".to_string(),
+            parameters: vec![located(ast::ParameterType::Private {
+                variable_type: ast::Type::UInt256,
+                variable: "a".to_string(),
+            })],
+            return_type: ast::Type::UInt256,
+            private_return: false,
+            statements: vec![located(if_statement), located(return_statement)],
+        };
+        ast::Program::GlobalStatements(vec![located(ast::GlobalStatementType::ContractStatement {
+            contract_name: "A".to_string(),
+            members: vec![located(function)],
+        })])
+    }
+
+    #[test]
+    fn test_visitor_mut_rewrites_in_place() {
+        let mut rewritten = program([1, 2, 3]);
+        NumberDoubler.visit_program_mut(&mut rewritten);
+        assert_eq!(rewritten, program([2, 4, 6]));
+    }
+}
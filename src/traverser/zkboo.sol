@@ -3,10 +3,11 @@ pragma solidity ^0.7.4;
 pragma experimental ABIEncoderV2;
 
 contract {contract_name} {
-    uint32 constant OZKB_NUMBER_OF_ROUNDS = 2;
+    uint32 constant OZKB_NUMBER_OF_ROUNDS = {rounds};
     uint32 constant OZKB_PUBLIC_BRANCHES = 2;
     uint32 constant OZKB_COMMITMENT_VIEW_LENGTH = 32;
     uint32 constant OZKB_RND_TAPE_SEED_LEN = 16;
+    uint32 constant OZKB_BLINDING_LEN = 16;
     uint32 constant OZKB_TOTAL_BRANCHES = 3;
 
     // Declaration of structure
@@ -22,11 +23,13 @@ contract {contract_name} {
         bytes rand_tape_seed;
         uint32[] in_data;
         uint32[] out_data;
+        bytes blinding;
     }
 
     struct IKosContext {
         IKosView ikos_view;
         uint32[8] randomness;
+        uint32 rand_block_ctr;
         uint32 used_rand_ctr;
         uint32 out_view_ctr;
     }
@@ -44,6 +47,7 @@ contract {contract_name} {
         bytes32 challenge;
         bytes two_views;
         IKosView[] response;
+        bytes context;
     }
 
     // 3D Vector
@@ -57,9 +61,10 @@ contract {contract_name} {
         return d * self.area + self.col * r + c;
     }
 
-    function generate_randomness(bytes memory key) internal pure returns (uint32[8] memory) {
+    // Block `counter` of a random tape: sha256(key || uint32(counter))
+    function generate_randomness(bytes memory key, uint32 counter) internal pure returns (uint32[8] memory) {
         uint32[8] memory randomness;
-        bytes memory r = toBytes(sha256(key));
+        bytes memory r = toBytes(sha256(abi.encodePacked(key, counter)));
         for (uint i = 0; i < 8; i++) {
             randomness[i] = toUint32(r, 4 * i);
         }
@@ -69,14 +74,8 @@ contract {contract_name} {
 
     function get_next_random_from_context(IKosContext memory ctx) internal pure returns (uint32) {
         if (ctx.used_rand_ctr == ctx.randomness.length) {
-            bytes memory b = new bytes(32);
-            for (uint i = 0; i < 8; i++) {
-                b[i * 4] = byte(uint8(ctx.randomness[i] >> 24));
-                b[i * 4 + 1] = byte(uint8(ctx.randomness[i] >> 16));
-                b[i * 4 + 2] = byte(uint8(ctx.randomness[i] >> 8));
-                b[i * 4 + 3] = byte(uint8(ctx.randomness[i]));
-            }
-            ctx.randomness = generate_randomness(b);
+            ctx.rand_block_ctr += 1;
+            ctx.randomness = generate_randomness(ctx.ikos_view.rand_tape_seed, ctx.rand_block_ctr);
             ctx.used_rand_ctr = 0;
         }
         uint32 rand = ctx.randomness[ctx.used_rand_ctr];
@@ -86,23 +85,45 @@ contract {contract_name} {
 
     // Ikos view
     function new_views(IKosView memory ikos_view) internal pure returns (IKosContext memory) {
-        uint32[8] memory randomness = generate_randomness(ikos_view.rand_tape_seed);
-        return IKosContext(ikos_view, randomness, 0, 0);
+        uint32[8] memory randomness = generate_randomness(ikos_view.rand_tape_seed, 0);
+        return IKosContext(ikos_view, randomness, 0, 0, 0);
+    }
+
+    function write_uint32(bytes memory b, uint offset, uint32 value) internal pure returns (uint) {
+        b[offset] = byte(uint8(value >> 24));
+        b[offset + 1] = byte(uint8(value >> 16));
+        b[offset + 2] = byte(uint8(value >> 8));
+        b[offset + 3] = byte(uint8(value));
+        return offset + 4;
     }
 
+    // sha256("zoker.commit" || blinding || rand_tape_seed || len(in_data) || in_data || len(out_data) || out_data)
     function commit_ikos_context(IKosContext memory ctx) internal pure returns (bytes32) {
-        bytes memory b = new bytes(OZKB_RND_TAPE_SEED_LEN + ctx.ikos_view.out_data.length * 4);
+        IKosView memory ikos_view = ctx.ikos_view;
+        bytes memory label = "zoker.commit";
+        bytes memory b = new bytes(
+            label.length + OZKB_BLINDING_LEN + OZKB_RND_TAPE_SEED_LEN
+            + 4 + ikos_view.in_data.length * 4 + 4 + ikos_view.out_data.length * 4
+        );
+        uint offset = 0;
+        for (uint i = 0; i < label.length; ++i) {
+            b[offset++] = label[i];
+        }
+        for (uint i = 0; i < OZKB_BLINDING_LEN; ++i) {
+            b[offset++] = ikos_view.blinding[i];
+        }
         for (uint i = 0; i < OZKB_RND_TAPE_SEED_LEN; ++i) {
-            b[i] = ctx.ikos_view.rand_tape_seed[i];
+            b[offset++] = ikos_view.rand_tape_seed[i];
+        }
+        offset = write_uint32(b, offset, uint32(ikos_view.in_data.length));
+        for (uint i = 0; i < ikos_view.in_data.length; i++) {
+            offset = write_uint32(b, offset, ikos_view.in_data[i]);
         }
-        for (uint i = 0; i < ctx.ikos_view.out_data.length; i++) {
-            b[OZKB_RND_TAPE_SEED_LEN + i * 4] = byte(uint8(ctx.ikos_view.out_data[i] >> 24));
-            b[OZKB_RND_TAPE_SEED_LEN + i * 4 + 1] = byte(uint8(ctx.ikos_view.out_data[i] >> 16));
-            b[OZKB_RND_TAPE_SEED_LEN + i * 4 + 2] = byte(uint8(ctx.ikos_view.out_data[i] >> 8));
-            b[OZKB_RND_TAPE_SEED_LEN + i * 4 + 3] = byte(uint8(ctx.ikos_view.out_data[i]));
+        offset = write_uint32(b, offset, uint32(ikos_view.out_data.length));
+        for (uint i = 0; i < ikos_view.out_data.length; i++) {
+            offset = write_uint32(b, offset, ikos_view.out_data[i]);
         }
-        bytes32 s = sha256(b);
-        return s;
+        return sha256(b);
     }
 
     // IkosVariable for Verifier
@@ -135,16 +156,25 @@ contract {contract_name} {
 
     function bit_xor(IKosVariable4V memory self, IKosVariable4V memory rhs) internal pure returns (IKosVariable4V memory) {
         uint32[OZKB_PUBLIC_BRANCHES] memory val;
+        if (is_empty_context(self) && is_empty_context(rhs)) {
+            for (uint i = 0; i < OZKB_PUBLIC_BRANCHES; ++i) {
+                val[i] = self.value[i] ^ rhs.value[i];
+            }
+            return IKosVariable4V(val, self.ctx, self.is_full);
+        }
+        if (is_empty_context(self)) {
+            return bit_xor(rhs, self);
+        }
         for (uint i = 0; i < OZKB_PUBLIC_BRANCHES; ++i) {
             val[i] = self.value[i] ^ rhs.value[i];
         }
         return IKosVariable4V(val, self.ctx, self.is_full);
     }
 
-    function bit_or(IKosVariable4V memory self, IKosVariable4V memory rhs) internal pure returns (IKosVariable4V memory) {
+    function rotr(IKosVariable4V memory self, uint32 n) internal pure returns (IKosVariable4V memory) {
         uint32[OZKB_PUBLIC_BRANCHES] memory val;
         for (uint i = 0; i < OZKB_PUBLIC_BRANCHES; ++i) {
-            val[i] = self.value[i] | rhs.value[i];
+            val[i] = (self.value[i] >> n) | (self.value[i] << (32 - n));
         }
         return IKosVariable4V(val, self.ctx, self.is_full);
     }
@@ -188,9 +218,7 @@ contract {contract_name} {
         ^ rand[0]
         ^ rand[1];
 
-        if (out != self.ctx[0].ikos_view.out_data[self.ctx[0].out_view_ctr]) {
-            revert();
-        }
+        self.ctx[0].ikos_view.out_data[self.ctx[0].out_view_ctr] = out;
         val[0] = out;
         val[1] = self.ctx[1].ikos_view.out_data[self.ctx[1].out_view_ctr];
         for (uint i = 0; i < OZKB_PUBLIC_BRANCHES; ++i) {
@@ -200,29 +228,22 @@ contract {contract_name} {
         return IKosVariable4V(val, self.ctx, self.is_full);
     }
 
-    function get_bit(uint32 x, uint i) internal pure returns (uint32) {
-        return (x >> i) & 1;
-    }
-
-    function gt_op(IKosVariable4V memory self, IKosVariable4V memory rhs) internal pure returns (IKosVariable4V memory) {
-        IKosVariable4V memory minus_rhs = add_op(negate(rhs), IKosVariable_new_value(1));
-        IKosVariable4V memory minus_gt = add_op(self, minus_rhs);
-
+    function and_const(IKosVariable4V memory self, uint32 mask) internal pure returns (IKosVariable4V memory) {
+        uint32[OZKB_PUBLIC_BRANCHES] memory val;
         for (uint i = 0; i < OZKB_PUBLIC_BRANCHES; ++i) {
-            uint val = minus_gt.value[i];
-            uint lsb = val >> 31;
-            if (lsb == 0) {
-                minus_gt.value[i] = 0xffffffff;
-            } else {
-                minus_gt.value[i] = 0;
-            }
+            val[i] = self.value[i] & mask;
         }
-        return minus_gt;
+        return IKosVariable4V(val, self.ctx, self.is_full);
+    }
+
+    function get_bit(uint32 x, uint i) internal pure returns (uint32) {
+        return (x >> i) & 1;
     }
 
     function add_op(IKosVariable4V memory self, IKosVariable4V memory rhs) internal pure returns (IKosVariable4V memory) {
         uint32[OZKB_PUBLIC_BRANCHES] memory val;
         if (is_empty_context(self) && is_empty_context(rhs)) {
+            // Wraps modulo 2^32 like the shared path; unchecked before 0.8.
             for (uint i = 0; i < OZKB_PUBLIC_BRANCHES; ++i) {
                 val[i] = self.value[i] + rhs.value[i];
             }
@@ -238,10 +259,7 @@ contract {contract_name} {
         for (uint i = 0; i < OZKB_PUBLIC_BRANCHES; ++i) {
             rand[i] = get_next_random_from_context(self.ctx[i]);
         }
-        for (uint i = 0; i < OZKB_PUBLIC_BRANCHES; ++i) {
-            out[i] = self.ctx[i].ikos_view.out_data[self.ctx[i].out_view_ctr];
-            self.ctx[i].out_view_ctr += 1;
-        }
+        out[1] = self.ctx[1].ikos_view.out_data[self.ctx[1].out_view_ctr];
         for (uint i = 0; i < 31; ++i) {
             for (uint j = 0; j < OZKB_PUBLIC_BRANCHES; ++j) {
                 a[j] = get_bit((self.value[j] ^ out[j]), i);
@@ -249,10 +267,11 @@ contract {contract_name} {
             }
 
             uint32 c = (a[0] & b[1]) ^ (a[1] & b[0]) ^ get_bit(rand[1], i);
-            if (c ^ (a[0] & b[0]) ^ (get_bit(out[0], i)) ^ (get_bit(rand[0], i))
-                != get_bit(out[0], i + 1)) {
-                revert();
-            }
+            out[0] |= (c ^ (a[0] & b[0]) ^ get_bit(out[0], i) ^ get_bit(rand[0], i)) << uint32(i + 1);
+        }
+        self.ctx[0].ikos_view.out_data[self.ctx[0].out_view_ctr] = out[0];
+        for (uint i = 0; i < OZKB_PUBLIC_BRANCHES; ++i) {
+            self.ctx[i].out_view_ctr += 1;
         }
         for (uint i = 0; i < OZKB_PUBLIC_BRANCHES; ++i) {
             val[i] = self.value[i] ^ rhs.value[i] ^ out[i];
@@ -261,22 +280,32 @@ contract {contract_name} {
     }
 
     // ZKBoo+
+    // Two bits at a time of sha256(commit || uint32(counter)), most
+    // significant first, rejecting values of OZKB_TOTAL_BRANCHES or more.
     function ZKBoo_choose_index_from_challenge(bytes32 commit) internal pure returns (uint[OZKB_NUMBER_OF_ROUNDS] memory) {
         uint[OZKB_NUMBER_OF_ROUNDS] memory res;
-        uint val = 0;
-        for (uint i = 0; i < 4; ++i) {
-            val = val * 16 + uint8(commit[i]);
-        }
-        for (uint i = 0; i < OZKB_NUMBER_OF_ROUNDS; ++i) {
-            res[i] = val % OZKB_TOTAL_BRANCHES;
-            val /= OZKB_TOTAL_BRANCHES;
+        uint round = 0;
+        for (uint32 counter = 0; round < OZKB_NUMBER_OF_ROUNDS; ++counter) {
+            bytes32 stream = sha256(abi.encodePacked(commit, counter));
+            for (uint i = 0; i < 128 && round < OZKB_NUMBER_OF_ROUNDS; ++i) {
+                uint index = (uint8(stream[i / 4]) >> (6 - 2 * (i % 4))) & 3;
+                if (index < OZKB_TOTAL_BRANCHES) {
+                    res[round++] = index;
+                }
+            }
         }
         return res;
     }
 
 {functions}
 
-    function ZKBoo_verify(VerifyingProof memory proof) internal pure returns (bool) {
+    // `circuit` is the compiled function whose digest is `circuit_digest`;
+    // every entry point passes its own pair.
+    function ZKBoo_verify(
+        VerifyingProof memory proof,
+        bytes32 circuit_digest,
+        function (IKosVariable4V[] memory, uint32[] memory) internal pure returns (IKosVariable4V[] memory) circuit
+    ) internal pure returns (bool) {
         uint[OZKB_NUMBER_OF_ROUNDS] memory index_vec = ZKBoo_choose_index_from_challenge(proof.challenge);
         _3DVector memory vec_view = _3DVector_new(proof.output.length, OZKB_NUMBER_OF_ROUNDS, OZKB_TOTAL_BRANCHES);
         uint8[OZKB_NUMBER_OF_ROUNDS * OZKB_TOTAL_BRANCHES * OZKB_COMMITMENT_VIEW_LENGTH] memory three_views;
@@ -286,21 +315,24 @@ contract {contract_name} {
             IKosContext[OZKB_PUBLIC_BRANCHES] memory ctx;
             for (uint party = 0; party < OZKB_PUBLIC_BRANCHES; ++party) {
                 ctx[party] = new_views(proof.response[round * OZKB_PUBLIC_BRANCHES + party]);
-                if (ctx[party].ikos_view.in_data.length == 0) {
-                    ctx[party].ikos_view.in_data = new uint32[](proof.input_len);
-                }
             }
+            // Input shares of parties 0 and 1 come from their tapes and
+            // replace whatever the proof sent; the commitment binds them.
             if (index_vec[round] == 0) {
+                ctx[0].ikos_view.in_data = new uint32[](proof.input_len);
                 for (uint i = 0; i < proof.input_len; ++i) {
                     uint32 data = get_next_random_from_context(ctx[0]);
                     ctx[0].ikos_view.in_data[i] = data;
                 }
             } else if (index_vec[round] == 1) {
+                ctx[1].ikos_view.in_data = new uint32[](proof.input_len);
                 for (uint i = 0; i < proof.input_len; ++i) {
                     uint32 data = get_next_random_from_context(ctx[1]);
                     ctx[1].ikos_view.in_data[i] = data;
                 }
             } else if (index_vec[round] == 2) {
+                ctx[0].ikos_view.in_data = new uint32[](proof.input_len);
+                ctx[1].ikos_view.in_data = new uint32[](proof.input_len);
                 for (uint i = 0; i < proof.input_len; ++i) {
                     uint32 data = get_next_random_from_context(ctx[0]);
                     ctx[0].ikos_view.in_data[i] = data;
@@ -310,6 +342,19 @@ contract {contract_name} {
             } else {
                 revert();
             }
+            for (uint party = 0; party < OZKB_PUBLIC_BRANCHES; ++party) {
+                require(ctx[party].ikos_view.in_data.length == proof.input_len);
+            }
+            // The first opened view is recomputed gate by gate; of the
+            // second only the gate outputs are sent. Both get room for
+            // their output shares.
+            uint gates = ctx[1].ikos_view.out_data.length;
+            ctx[0].ikos_view.out_data = new uint32[](gates + proof.output.length);
+            uint32[] memory sent = ctx[1].ikos_view.out_data;
+            ctx[1].ikos_view.out_data = new uint32[](gates + proof.output.length);
+            for (uint i = 0; i < gates; ++i) {
+                ctx[1].ikos_view.out_data[i] = sent[i];
+            }
 
             IKosVariable4V[] memory ikos_input = new IKosVariable4V[](proof.input_len);
             for (uint i = 0; i < proof.input_len; ++i) {
@@ -320,12 +365,12 @@ contract {contract_name} {
                 ikos_input[i] = IKosVariable_new_share(shares, ctx);
             }
 
-            IKosVariable4V[] memory ikos_output = _{circuit}(ikos_input, proof.input_pub);
+            IKosVariable4V[] memory ikos_output = circuit(ikos_input, proof.input_pub);
+            require(ctx[1].out_view_ctr == gates);
             for (uint branch = 0; branch < OZKB_PUBLIC_BRANCHES; ++branch) {
                 for (uint j = 0; j < ikos_output.length; ++j) {
-                    if (ikos_output[j].value[branch] != ctx[branch].ikos_view.out_data[ctx[branch].out_view_ctr]) {
-                        revert();
-                    }
+                    ctx[branch].ikos_view.out_data[ctx[branch].out_view_ctr] = ikos_output[j].value[branch];
+                    ctx[branch].out_view_ctr += 1;
                 }
             }
 
@@ -401,7 +446,7 @@ contract {contract_name} {
                 }
             }
         }
-        bytes32 random_oracle = query_random_oracle(proof.input_len, proof.output.length, vec_view.data, three_views);
+        bytes32 random_oracle = query_random_oracle(circuit_digest, proof, vec_view.data, three_views);
         for (uint i = 0; i < 32; ++i) {
             if (proof.challenge[i] != random_oracle[i]) {
                 return false;
@@ -410,33 +455,47 @@ contract {contract_name} {
         return true;
     }
 
+    // sha256("zoker.fiat-shamir" || circuit_digest || len(context) || context || input_len
+    //        || len(input_pub) || input_pub || len(output) || output || out_data || three_views)
     function query_random_oracle(
-        uint input_len,
-        uint output_len,
+        bytes32 circuit_digest,
+        VerifyingProof memory proof,
         uint[] memory vec_view_data,
         uint8[OZKB_NUMBER_OF_ROUNDS * OZKB_TOTAL_BRANCHES * OZKB_COMMITMENT_VIEW_LENGTH] memory three_views
     ) internal pure returns (bytes32) {
-        bytes memory b = new bytes(8 + 4 * vec_view_data.length + three_views.length);
-        b[0] = byte(uint8(input_len >> 24));
-        b[1] = byte(uint8(input_len >> 16));
-        b[2] = byte(uint8(input_len >> 8));
-        b[3] = byte(uint8(input_len));
-        b[4] = byte(uint8(output_len >> 24));
-        b[5] = byte(uint8(output_len >> 16));
-        b[6] = byte(uint8(output_len >> 8));
-        b[7] = byte(uint8(output_len));
+        bytes memory label = "zoker.fiat-shamir";
+        bytes memory b = new bytes(
+            label.length + 32 + 4 + proof.context.length + 4
+            + 4 + 4 * proof.input_pub.length + 4 + 4 * proof.output.length
+            + 4 * vec_view_data.length + three_views.length
+        );
+        uint offset = 0;
+        for (uint i = 0; i < label.length; ++i) {
+            b[offset++] = label[i];
+        }
+        for (uint i = 0; i < 32; ++i) {
+            b[offset++] = circuit_digest[i];
+        }
+        offset = write_uint32(b, offset, uint32(proof.context.length));
+        for (uint i = 0; i < proof.context.length; ++i) {
+            b[offset++] = proof.context[i];
+        }
+        offset = write_uint32(b, offset, uint32(proof.input_len));
+        offset = write_uint32(b, offset, uint32(proof.input_pub.length));
+        for (uint i = 0; i < proof.input_pub.length; i++) {
+            offset = write_uint32(b, offset, proof.input_pub[i]);
+        }
+        offset = write_uint32(b, offset, uint32(proof.output.length));
+        for (uint i = 0; i < proof.output.length; i++) {
+            offset = write_uint32(b, offset, proof.output[i]);
+        }
         for (uint i = 0; i < vec_view_data.length; i++) {
-            b[8 + i * 4] = byte(uint8(vec_view_data[i] >> 24));
-            b[9 + i * 4] = byte(uint8(vec_view_data[i] >> 16));
-            b[10 + i * 4] = byte(uint8(vec_view_data[i] >> 8));
-            b[11 + i * 4] = byte(uint8(vec_view_data[i]));
+            offset = write_uint32(b, offset, uint32(vec_view_data[i]));
         }
-        uint sp = 8 + vec_view_data.length * 4;
         for (uint i = 0; i < three_views.length; i++) {
-            b[sp + i] = byte(three_views[i]);
+            b[offset++] = byte(three_views[i]);
         }
-        bytes32 s = sha256(b);
-        return s;
+        return sha256(b);
     }
 
     // For type
@@ -1,4 +1,9 @@
+pub mod bitsliced;
+pub mod circuit;
+pub mod cost;
 pub mod ikos;
-mod utils;
+pub mod optimizer;
+pub mod transcript;
+pub mod utils;
 mod vector;
 pub mod zkboo;
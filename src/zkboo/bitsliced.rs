@@ -0,0 +1,393 @@
+//! Bitsliced prover engine: evaluates up to 32 rounds at once.
+//!
+//! A share of a word is stored as 32 bit planes, where bit `r` of plane `b`
+//! is bit `b` of the share in round `r`. XOR and AND gates then act on all
+//! rounds with one machine word per bit, and the bit-serial adder walks 31
+//! plane positions instead of 31 bits of every round. Random words are
+//! drawn from the per-round tapes and transposed into planes, and gate
+//! outputs are transposed back into per-round views, so a proof is the
+//! same as with the per-round engine.
+//!
+//! `cargo test --release bench_bitsliced -- --ignored --nocapture` compares
+//! both engines.
+
+use crate::zkboo::ikos::{get_next_random_from_context, IKosContext, SharedContexts, Variable4P};
+
+/// Rounds packed into one bitsliced word.
+pub const BITSLICE_ROUNDS: usize = 32;
+
+const PARTIES: usize = 3;
+
+type Planes = [u32; 32];
+
+/// A secret-shared word of up to 32 rounds. The contexts hold the three
+/// parties of every round, round-major; constants have none.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bitsliced4P {
+    pub planes: Vec<Planes>,
+    ctx: SharedContexts,
+}
+
+/// Transposes a 32x32 bit matrix in place: bit `c` of row `r` moves to
+/// bit `r` of row `c`.
+fn transpose32(a: &mut Planes) {
+    let mut j = 16;
+    let mut m: u32 = 0x0000_ffff;
+    while j != 0 {
+        let mut k = 0;
+        while k < 32 {
+            let t = ((a[k] >> j) ^ a[k + j]) & m;
+            a[k] ^= t << j;
+            a[k + j] ^= t;
+            k = (k + j + 1) & !j;
+        }
+        j >>= 1;
+        m ^= m << j;
+    }
+}
+
+/// Bit planes of one word per round.
+pub fn to_planes(words: &[u32]) -> Planes {
+    let mut planes = [0; 32];
+    planes[..words.len()].copy_from_slice(words);
+    transpose32(&mut planes);
+    planes
+}
+
+/// The word of each of the first `rounds` rounds.
+pub fn from_planes(planes: &Planes, rounds: usize) -> Vec<u32> {
+    let mut words = *planes;
+    transpose32(&mut words);
+    words[..rounds].to_vec()
+}
+
+fn broadcast(value: u32) -> Planes {
+    let mut planes = [0; 32];
+    for (bit, plane) in planes.iter_mut().enumerate() {
+        if (value >> bit) & 1 != 0 {
+            *plane = u32::MAX;
+        }
+    }
+    planes
+}
+
+fn map_planes(planes: &[Planes], f: impl Fn(&Planes) -> Planes) -> Vec<Planes> {
+    planes.iter().map(f).collect()
+}
+
+impl Bitsliced4P {
+    pub fn new_share(planes: Vec<Planes>, ctx: SharedContexts) -> Self {
+        Bitsliced4P { planes, ctx }
+    }
+
+    fn is_empty_context(&self) -> bool {
+        self.ctx.lock().is_empty()
+    }
+
+    fn rounds(&self) -> usize {
+        self.ctx.lock().len() / PARTIES
+    }
+
+    // The next word of every round's tape, as planes for each party.
+    fn next_random(&self) -> Vec<Planes> {
+        let mut contexts = self.ctx.lock();
+        (0..PARTIES)
+            .map(|party| {
+                let words: Vec<u32> = contexts
+                    .iter_mut()
+                    .skip(party)
+                    .step_by(PARTIES)
+                    .map(|ctx| get_next_random_from_context(ctx).unwrap())
+                    .collect();
+                to_planes(&words)
+            })
+            .collect()
+    }
+
+    // Appends a gate output to the view of every party in every round.
+    fn push_out_data(&self, out: &[Planes]) {
+        let rounds = self.rounds();
+        let mut contexts = self.ctx.lock();
+        for (party, planes) in out.iter().enumerate() {
+            for (round, word) in from_planes(planes, rounds).into_iter().enumerate() {
+                contexts[round * PARTIES + party].ikos_view.out_data.push(word);
+            }
+        }
+    }
+
+    fn with_planes(self, planes: Vec<Planes>) -> Self {
+        Bitsliced4P { planes, ctx: self.ctx }
+    }
+
+    // Context of `rhs` if `self` is a constant.
+    fn join(self, rhs: &Bitsliced4P) -> Self {
+        if self.is_empty_context() {
+            Bitsliced4P { planes: self.planes, ctx: rhs.ctx.clone() }
+        } else {
+            self
+        }
+    }
+
+    fn constant(&self) -> u32 {
+        (0..32).fold(0, |acc, bit| acc | ((self.planes[0][bit] & 1) << bit))
+    }
+}
+
+impl Variable4P for Bitsliced4P {
+    fn new_value(value: u32) -> Self {
+        Bitsliced4P {
+            planes: vec![broadcast(value); PARTIES],
+            ctx: SharedContexts::default(),
+        }
+    }
+
+    fn negate(self) -> Self {
+        let planes = map_planes(&self.planes, |p| {
+            let mut res = *p;
+            res.iter_mut().for_each(|w| *w = !*w);
+            res
+        });
+        self.with_planes(planes)
+    }
+
+    fn xor(self, rhs: &Self) -> Self {
+        let mut res = self.join(rhs);
+        for (planes, rhs) in res.planes.iter_mut().zip(&rhs.planes) {
+            for (w, r) in planes.iter_mut().zip(rhs) {
+                *w ^= r;
+            }
+        }
+        res
+    }
+
+    fn rotate_right(self, n: u32) -> Self {
+        let n = n as usize % 32;
+        let planes = map_planes(&self.planes, |p| {
+            let mut res = [0; 32];
+            for (bit, w) in res.iter_mut().enumerate() {
+                *w = p[(bit + n) % 32];
+            }
+            res
+        });
+        self.with_planes(planes)
+    }
+
+    fn rshift(self, n: u32) -> Self {
+        let n = n as usize;
+        let planes = map_planes(&self.planes, |p| {
+            let mut res = [0; 32];
+            for (bit, w) in res.iter_mut().enumerate().take(32usize.saturating_sub(n)) {
+                *w = p[bit + n];
+            }
+            res
+        });
+        self.with_planes(planes)
+    }
+
+    fn lshift(self, n: u32) -> Self {
+        let n = n as usize;
+        let planes = map_planes(&self.planes, |p| {
+            let mut res = [0; 32];
+            for (bit, w) in res.iter_mut().enumerate().skip(n) {
+                *w = p[bit - n];
+            }
+            res
+        });
+        self.with_planes(planes)
+    }
+
+    fn bit_and(self, rhs: &Self) -> Self {
+        if self.is_empty_context() && rhs.is_empty_context() {
+            let planes = (0..PARTIES)
+                .map(|i| {
+                    let mut res = self.planes[i];
+                    res.iter_mut().zip(&rhs.planes[i]).for_each(|(w, r)| *w &= r);
+                    res
+                })
+                .collect();
+            return self.with_planes(planes);
+        }
+        if self.is_empty_context() {
+            return rhs.clone().bit_and(&self);
+        }
+
+        let rand = self.next_random();
+        let (a, b) = (&self.planes, &rhs.planes);
+        let mut out = vec![[0; 32]; PARTIES];
+        for (i, planes) in out.iter_mut().enumerate() {
+            let j = (i + 1) % PARTIES;
+            for bit in 0..32 {
+                planes[bit] = (a[i][bit] & b[j][bit])
+                    ^ (a[j][bit] & b[i][bit])
+                    ^ (a[i][bit] & b[i][bit])
+                    ^ rand[i][bit]
+                    ^ rand[j][bit];
+            }
+        }
+        self.push_out_data(&out);
+        self.with_planes(out)
+    }
+
+    fn and_const(self, mask: u32) -> Self {
+        let mask = broadcast(mask);
+        let planes = map_planes(&self.planes, |p| {
+            let mut res = *p;
+            res.iter_mut().zip(&mask).for_each(|(w, m)| *w &= m);
+            res
+        });
+        self.with_planes(planes)
+    }
+
+    fn add_op(self, rhs: &Self) -> Self {
+        if self.is_empty_context() && rhs.is_empty_context() {
+            // constant calculation
+            return Bitsliced4P::new_value(self.constant().wrapping_add(rhs.constant()));
+        }
+        if self.is_empty_context() {
+            return rhs.clone().add_op(&self);
+        }
+
+        let rand = self.next_random();
+        let (x, y) = (&self.planes, &rhs.planes);
+        let mut out = vec![[0; 32]; PARTIES];
+        for bit in 0..31 {
+            let mut a = [0; PARTIES];
+            let mut b = [0; PARTIES];
+            for j in 0..PARTIES {
+                a[j] = x[j][bit] ^ out[j][bit];
+                b[j] = y[j][bit] ^ out[j][bit];
+            }
+            for j in 0..PARTIES {
+                let k = (j + 1) % PARTIES;
+                let c = (a[j] & b[k]) ^ (a[k] & b[j]) ^ rand[k][bit];
+                out[j][bit + 1] = c ^ (a[j] & b[j]) ^ out[j][bit] ^ rand[j][bit];
+            }
+        }
+        self.push_out_data(&out);
+
+        let mut planes = out;
+        for (j, plane) in planes.iter_mut().enumerate() {
+            for bit in 0..32 {
+                plane[bit] ^= x[j][bit] ^ y[j][bit];
+            }
+        }
+        self.with_planes(planes)
+    }
+}
+
+/// Contexts for a batch of rounds, round-major, three parties each.
+pub fn batch_contexts(rounds: Vec<Vec<IKosContext>>) -> SharedContexts {
+    SharedContexts::new(rounds.into_iter().flatten().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zkboo::ikos::{party_seeds, IKosVariable4P, MasterSeeder, TapeSeeder};
+    use crate::zkboo::zkboo::{Circuit4PTrait, Engine, ProvingProof, ZkBoo};
+    use std::time::Instant;
+
+    #[test]
+    fn test_transpose_round_trips() {
+        let words: Vec<u32> = (0..32).map(|i| 0x9e37_79b9u32.wrapping_mul(i + 1)).collect();
+        let planes = to_planes(&words);
+        for (bit, plane) in planes.iter().enumerate() {
+            for (round, word) in words.iter().enumerate() {
+                assert_eq!((plane >> round) & 1, (word >> bit) & 1);
+            }
+        }
+        assert_eq!(from_planes(&planes, 32), words);
+    }
+
+    // Every gate of both engines, on the same tapes, gives the same views.
+    #[test]
+    fn test_gates_match_per_round_engine() {
+        let mut seeder = MasterSeeder::new([9; 32]);
+        let rounds = 5;
+        let contexts: Vec<Vec<IKosContext>> = (0..rounds)
+            .map(|round| {
+                let seed = seeder.round_seed(round);
+                (0..PARTIES)
+                    .map(|party| {
+                        let (tape_seed, blinding) = party_seeds(&seed, party);
+                        IKosContext::new_with_seed(tape_seed, blinding)
+                    })
+                    .collect()
+            })
+            .collect();
+        let batch = batch_contexts(contexts.clone());
+        let (x, y) = (0x1234_5678, 0xfedc_ba98);
+        let shares = |value: u32, round: usize| vec![value ^ round as u32, round as u32 * 7, value ^ (round as u32 * 7) ^ round as u32];
+
+        let sliced_x = Bitsliced4P::new_share(
+            (0..PARTIES).map(|p| to_planes(&(0..rounds).map(|r| shares(x, r)[p]).collect::<Vec<_>>())).collect(),
+            batch.clone(),
+        );
+        let sliced_y = Bitsliced4P::new_share(
+            (0..PARTIES).map(|p| to_planes(&(0..rounds).map(|r| shares(y, r)[p]).collect::<Vec<_>>())).collect(),
+            batch.clone(),
+        );
+        let sliced = sliced_x.clone().add_op(&sliced_y).bit_and(&sliced_x).xor(&sliced_y).and_const(0x0ff0_f00f).rotate_right(3);
+
+        for (round, ctx) in contexts.into_iter().enumerate() {
+            let ctx = SharedContexts::new(ctx);
+            let px = IKosVariable4P::new_share(shares(x, round), ctx.clone());
+            let py = IKosVariable4P::new_share(shares(y, round), ctx.clone());
+            let plain = px.clone().add_op(&py).bit_and(&px).xor(&py).and_const(0x0ff0_f00f).rotate_right(3);
+            for party in 0..PARTIES {
+                assert_eq!(from_planes(&sliced.planes[party], rounds)[round], plain.value[party]);
+                assert_eq!(
+                    batch.lock()[round * PARTIES + party].ikos_view,
+                    ctx.lock()[party].ikos_view
+                );
+            }
+        }
+    }
+
+    struct RepeatedSum;
+
+    impl RepeatedSum {
+        fn run<V: Variable4P>(input: Vec<V>) -> Vec<V> {
+            let all = input.iter().cycle().take(8 * input.len()).skip(1);
+            vec![all.fold(input[0].clone(), |acc, x| acc.add_op(x).xor(x))]
+        }
+    }
+
+    impl Circuit4PTrait for RepeatedSum {
+        fn circuit(&self, input: Vec<IKosVariable4P>, _input_pub: &[u32]) -> Vec<IKosVariable4P> {
+            Self::run(input)
+        }
+
+        fn circuit_bitsliced(&self, input: Vec<Bitsliced4P>, _input_pub: &[u32]) -> Option<Vec<Bitsliced4P>> {
+            Some(Self::run(input))
+        }
+    }
+
+    fn prove(zk_boo: &ZkBoo, engine: Engine) -> crate::zkboo::zkboo::Proof {
+        let proving = ProvingProof::new(vec![1, 2, 3, 4], vec![], 1, Box::new(RepeatedSum))
+            .with_seeder(Box::new(MasterSeeder::new([4; 32])))
+            .with_engine(engine);
+        zk_boo.prove(proving).unwrap()
+    }
+
+    #[test]
+    fn test_bitsliced_proof_matches_per_round_proof() {
+        // 40 rounds: one full batch and one partial.
+        let zk_boo = ZkBoo::new(40, 3, 2, 32);
+        assert_eq!(prove(&zk_boo, Engine::Bitsliced), prove(&zk_boo, Engine::PerRound));
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_bitsliced_engine() {
+        let zk_boo = ZkBoo::new(256, 3, 2, 32);
+        let mut timings = vec![];
+        for &engine in [Engine::PerRound, Engine::Bitsliced].iter() {
+            let start = Instant::now();
+            prove(&zk_boo, engine);
+            timings.push(start.elapsed());
+            eprintln!("{:?}: {:?}", engine, start.elapsed());
+        }
+        eprintln!("speedup: {:.2}x", timings[0].as_secs_f64() / timings[1].as_secs_f64());
+    }
+}
@@ -0,0 +1,835 @@
+use crate::zkboo::bitsliced::Bitsliced4P;
+use crate::zkboo::ikos::{IKosError, IKosResult, IKosVariable4P, IKosVariable4V, Variable4P};
+use crate::zkboo::zkboo::{Circuit4PTrait, Circuit4VTrait};
+
+mod keccak;
+mod merkle;
+mod sha256;
+
+pub use merkle::NODE_WORDS;
+
+/// Index of the gate whose output a wire carries.
+pub type Wire = usize;
+
+/// Carry chain length of the adder gadget, used as its AND-depth.
+pub const ADDER_DEPTH: usize = 31;
+
+/// A gate over 32-bit words. Every gate defines one wire, the wire with
+/// the gate's index, and only reads wires defined before it.
+///
+/// `And` and `Add` are the only gates that cost view data: each writes one
+/// word to every party's view. The others are computed locally by each
+/// party.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Gate {
+    /// Word `i` of the private input.
+    Input(usize),
+    /// Word `i` of the public input.
+    PublicInput(usize),
+    Const(u32),
+    Xor(Wire, Wire),
+    And(Wire, Wire),
+    /// AND with a constant, local to each party.
+    AndConst(Wire, u32),
+    Not(Wire),
+    RotateRight(Wire, u32),
+    ShiftRight(Wire, u32),
+    ShiftLeft(Wire, u32),
+    /// Addition modulo 2^32 through the IKOS adder gadget.
+    Add(Wire, Wire),
+}
+
+/// A flat circuit of word gates, compiled once from a function and then
+/// run by the prover engines, the native verifier and emitted as the
+/// Solidity verifier.
+///
+/// A circuit with checks has one more output word after the function's
+/// outputs, the error word: all ones where a check failed, zero if all
+/// passed. A proof is only accepted if its error word is zero.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Circuit {
+    gates: Vec<Gate>,
+    outputs: Vec<Wire>,
+    input_len: usize,
+    input_pub_len: usize,
+    has_error: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CircuitStats {
+    pub inputs: usize,
+    pub public_inputs: usize,
+    pub constants: usize,
+    pub xor_gates: usize,
+    pub and_gates: usize,
+    pub and_const_gates: usize,
+    pub not_gates: usize,
+    /// Rotations and shifts by a constant.
+    pub wiring_gates: usize,
+    pub add_gates: usize,
+    pub outputs: usize,
+    pub and_depth: usize,
+}
+
+impl CircuitStats {
+    /// Words each party writes to its view per round, outputs aside.
+    pub fn view_words(&self) -> usize {
+        self.and_gates + self.add_gates
+    }
+
+    /// Gates each party computes on its own shares.
+    pub fn local_gates(&self) -> usize {
+        self.xor_gates + self.and_const_gates + self.not_gates + self.wiring_gates
+    }
+}
+
+impl Circuit {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn gates(&self) -> &[Gate] {
+        &self.gates
+    }
+
+    pub fn outputs(&self) -> &[Wire] {
+        &self.outputs
+    }
+
+    pub fn input_len(&self) -> usize {
+        self.input_len
+    }
+
+    pub fn input_pub_len(&self) -> usize {
+        self.input_pub_len
+    }
+
+    pub fn set_outputs(&mut self, outputs: Vec<Wire>) {
+        self.outputs = outputs;
+        self.has_error = false;
+    }
+
+    /// Appends `error` to the outputs as the error word.
+    pub fn set_error(&mut self, error: Wire) {
+        self.outputs.push(error);
+        self.has_error = true;
+    }
+
+    pub fn error(&self) -> Option<Wire> {
+        self.outputs.last().copied().filter(|_| self.has_error)
+    }
+
+    /// Whether a proof with `output` passes the checks, that is whether
+    /// its error word, if there is one, is zero.
+    pub fn accepts(&self, output: &[u32]) -> bool {
+        output.len() == self.outputs.len() && !(self.has_error && output.last() != Some(&0))
+    }
+
+    /// An empty circuit over the given number of input words.
+    pub(crate) fn with_inputs(input_len: usize, input_pub_len: usize) -> Self {
+        Circuit {
+            input_len,
+            input_pub_len,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn push(&mut self, gate: Gate) -> Wire {
+        self.gates.push(gate);
+        self.gates.len() - 1
+    }
+
+    /// The next word of the private input.
+    pub fn input(&mut self) -> Wire {
+        self.input_len += 1;
+        self.push(Gate::Input(self.input_len - 1))
+    }
+
+    /// The next word of the public input.
+    pub fn public_input(&mut self) -> Wire {
+        self.input_pub_len += 1;
+        self.push(Gate::PublicInput(self.input_pub_len - 1))
+    }
+
+    pub fn constant(&mut self, value: u32) -> Wire {
+        self.push(Gate::Const(value))
+    }
+
+    pub fn xor(&mut self, a: Wire, b: Wire) -> Wire {
+        self.push(Gate::Xor(a, b))
+    }
+
+    pub fn and(&mut self, a: Wire, b: Wire) -> Wire {
+        self.push(Gate::And(a, b))
+    }
+
+    pub fn and_const(&mut self, a: Wire, mask: u32) -> Wire {
+        self.push(Gate::AndConst(a, mask))
+    }
+
+    pub fn not(&mut self, a: Wire) -> Wire {
+        self.push(Gate::Not(a))
+    }
+
+    pub fn rotate_right(&mut self, a: Wire, n: u32) -> Wire {
+        self.push(Gate::RotateRight(a, n % 32))
+    }
+
+    /// Shifts by 32 or more give zero, as they do in Solidity.
+    pub fn rshift(&mut self, a: Wire, n: u32) -> Wire {
+        if n >= 32 {
+            return self.constant(0);
+        }
+        self.push(Gate::ShiftRight(a, n))
+    }
+
+    pub fn lshift(&mut self, a: Wire, n: u32) -> Wire {
+        if n >= 32 {
+            return self.constant(0);
+        }
+        self.push(Gate::ShiftLeft(a, n))
+    }
+
+    pub fn add(&mut self, a: Wire, b: Wire) -> Wire {
+        self.push(Gate::Add(a, b))
+    }
+
+    /// `a + b` modulo 2^32 and a mask that is all ones if the sum
+    /// overflowed, one AND gate on top of the adder.
+    ///
+    /// The carry into every bit is `(a + b) ^ a ^ b`; the carry out of bit
+    /// 31 is the majority of the top bits of `a`, `b` and that carry.
+    pub fn add_checked(&mut self, a: Wire, b: Wire) -> (Wire, Wire) {
+        let sum = self.add(a, b);
+        let ab = self.xor(a, b);
+        let carry = self.xor(sum, ab);
+        let (ac, bc) = (self.xor(a, carry), self.xor(b, carry));
+        let both = self.and(ac, bc);
+        let majority = self.xor(both, carry);
+        let overflow = self.bit_mask(majority, 31);
+        (sum, overflow)
+    }
+
+    /// `a - b` modulo 2^32, as `a + !b + 1`.
+    pub fn sub(&mut self, a: Wire, b: Wire) -> Wire {
+        let not_b = self.not(b);
+        let one = self.constant(1);
+        let minus_b = self.add(not_b, one);
+        self.add(a, minus_b)
+    }
+
+    /// `a - b` modulo 2^32 and a mask that is all ones if `b > a`, two
+    /// AND gates on top of the adders.
+    ///
+    /// `a + !b + 1` carries out of bit 31 exactly when there is no borrow;
+    /// at most one of the two additions can carry.
+    pub fn sub_checked(&mut self, a: Wire, b: Wire) -> (Wire, Wire) {
+        let not_b = self.not(b);
+        let (partial, carry1) = self.add_checked(a, not_b);
+        let one = self.constant(1);
+        let (diff, carry2) = self.add_checked(partial, one);
+        let carry = self.xor(carry1, carry2);
+        (diff, self.not(carry))
+    }
+
+    /// `a * b` modulo 2^32 by shift and add: 32 AND gates selecting the
+    /// partial products and 32 additions.
+    pub fn mul(&mut self, a: Wire, b: Wire) -> Wire {
+        let mut acc = self.constant(0);
+        for i in 0..32 {
+            let mask = self.bit_mask(b, i);
+            let partial = self.and(a, mask);
+            let shifted = self.lshift(partial, i);
+            acc = self.add(acc, shifted);
+        }
+        acc
+    }
+
+    /// `a * b` modulo 2^32 and a mask that is all ones if the product
+    /// does not fit in 32 bits.
+    ///
+    /// The high word of the product is a sum of the bits each partial
+    /// product shifts out and the carries of the low word; it is zero
+    /// exactly when all of those are, so they are ORed instead of added.
+    pub fn mul_checked(&mut self, a: Wire, b: Wire) -> (Wire, Wire) {
+        let mut acc = self.constant(0);
+        let mut high = self.constant(0);
+        for i in 0..32 {
+            let mask = self.bit_mask(b, i);
+            let partial = self.and(a, mask);
+            let shifted = self.lshift(partial, i);
+            let (sum, carry) = self.add_checked(acc, shifted);
+            let shifted_out = self.rshift(partial, 32 - i);
+            let lost = self.or(shifted_out, carry);
+            high = self.or(high, lost);
+            acc = sum;
+        }
+        let zero = self.constant(0);
+        let fits = self.equal(high, zero);
+        (acc, self.not(fits))
+    }
+
+    /// `!(!a & !b)`, one AND gate.
+    pub fn or(&mut self, a: Wire, b: Wire) -> Wire {
+        let (na, nb) = (self.not(a), self.not(b));
+        let and = self.and(na, nb);
+        self.not(and)
+    }
+
+    /// `b` where `mask` is set and `a` elsewhere, one AND gate.
+    pub fn mux(&mut self, mask: Wire, a: Wire, b: Wire) -> Wire {
+        let diff = self.xor(a, b);
+        let picked = self.and(mask, diff);
+        self.xor(a, picked)
+    }
+
+    /// All ones if both words are equal, otherwise zero.
+    ///
+    /// Every bit of `!(a ^ b)` is ANDed together by folding the word onto
+    /// its own rotations, which costs five AND gates.
+    pub fn equal(&mut self, a: Wire, b: Wire) -> Wire {
+        let xor = self.xor(a, b);
+        let mut res = self.not(xor);
+        for &n in [16, 8, 4, 2, 1].iter() {
+            let rotated = self.rotate_right(res, n);
+            res = self.and(res, rotated);
+        }
+        res
+    }
+
+    /// All ones if `a > b` as unsigned words, otherwise zero.
+    ///
+    /// Compares bit by bit without an adder: `g` marks the bits where `a`
+    /// is greater and `e` those where both are equal, and five rounds
+    /// merge each bit with the next more significant block, doubling its
+    /// width, until bit 0 covers the word. That is 10 AND gates at depth
+    /// 6.
+    pub fn gt(&mut self, a: Wire, b: Wire) -> Wire {
+        let not_b = self.not(b);
+        let mut g = self.and(a, not_b);
+        let diff = self.xor(a, b);
+        let mut e = self.not(diff);
+        for &n in [1, 2, 4, 8, 16].iter() {
+            // A block is greater if its upper half is, or the upper half
+            // is equal and the lower half greater. Both cannot hold at
+            // once, so the OR is an XOR.
+            let (g_high, e_high) = (self.rshift(g, n), self.rshift(e, n));
+            let lower = self.and(e_high, g);
+            g = self.xor(g_high, lower);
+            if n < 16 {
+                e = self.and(e_high, e);
+            }
+        }
+        self.bit_mask(g, 0)
+    }
+
+    /// All ones if `lo <= x <= hi` as unsigned words, otherwise zero,
+    /// from two comparisons: 21 AND gates.
+    pub fn in_range(&mut self, x: Wire, lo: Wire, hi: Wire) -> Wire {
+        let below = self.gt(lo, x);
+        let above = self.gt(x, hi);
+        let out = self.or(below, above);
+        self.not(out)
+    }
+
+    /// All ones if bit `i` of `a` is set, otherwise zero.
+    ///
+    /// The bit is spread over the word with shifts and XORs, which stay
+    /// local to each party.
+    pub fn bit_mask(&mut self, a: Wire, i: u32) -> Wire {
+        let top = self.lshift(a, 31 - i);
+        let mut mask = self.rshift(top, 31);
+        for &n in [1, 2, 4, 8, 16].iter() {
+            let shifted = self.lshift(mask, n);
+            mask = self.xor(mask, shifted);
+        }
+        mask
+    }
+
+    /// `a >> amount` for an amount only known at run time, zero if it is
+    /// 32 or more. A barrel shifter: one selection per bit of the amount,
+    /// then a comparison of the remaining bits with zero, 11 AND gates in
+    /// all.
+    pub fn rshift_var(&mut self, a: Wire, amount: Wire) -> Wire {
+        let shifted = self.barrel(a, amount, |c, w, n| c.rshift(w, n));
+        self.zero_if_out_of_range(shifted, amount)
+    }
+
+    /// `a << amount`, see `rshift_var`.
+    pub fn lshift_var(&mut self, a: Wire, amount: Wire) -> Wire {
+        let shifted = self.barrel(a, amount, |c, w, n| c.lshift(w, n));
+        self.zero_if_out_of_range(shifted, amount)
+    }
+
+    /// `a` rotated right by `amount % 32`, 5 AND gates.
+    pub fn rotate_right_var(&mut self, a: Wire, amount: Wire) -> Wire {
+        self.barrel(a, amount, |c, w, n| c.rotate_right(w, n))
+    }
+
+    /// `a` rotated left by `amount % 32`, 5 AND gates.
+    pub fn rotate_left_var(&mut self, a: Wire, amount: Wire) -> Wire {
+        self.barrel(a, amount, |c, w, n| c.rotate_right(w, 32 - n))
+    }
+
+    // Applies `step` by 1, 2, 4, 8 and 16 where the matching bit of
+    // `amount` is set.
+    fn barrel<F>(&mut self, a: Wire, amount: Wire, step: F) -> Wire
+    where
+        F: Fn(&mut Self, Wire, u32) -> Wire,
+    {
+        let mut res = a;
+        for i in 0..5 {
+            let mask = self.bit_mask(amount, i);
+            let stepped = step(self, res, 1 << i);
+            res = self.mux(mask, res, stepped);
+        }
+        res
+    }
+
+    fn zero_if_out_of_range(&mut self, a: Wire, amount: Wire) -> Wire {
+        let high = self.rshift(amount, 5);
+        let zero = self.constant(0);
+        let in_range = self.equal(high, zero);
+        self.and(a, in_range)
+    }
+
+    pub fn stats(&self) -> CircuitStats {
+        let mut stats = CircuitStats {
+            inputs: self.input_len,
+            public_inputs: self.input_pub_len,
+            outputs: self.outputs.len(),
+            ..Default::default()
+        };
+        let mut depth = vec![0; self.gates.len()];
+        for (i, gate) in self.gates.iter().enumerate() {
+            depth[i] = match *gate {
+                Gate::Input(_) | Gate::PublicInput(_) => 0,
+                Gate::Const(_) => {
+                    stats.constants += 1;
+                    0
+                }
+                Gate::Xor(a, b) => {
+                    stats.xor_gates += 1;
+                    depth[a].max(depth[b])
+                }
+                Gate::And(a, b) => {
+                    stats.and_gates += 1;
+                    depth[a].max(depth[b]) + 1
+                }
+                Gate::AndConst(a, _) => {
+                    stats.and_const_gates += 1;
+                    depth[a]
+                }
+                Gate::Not(a) => {
+                    stats.not_gates += 1;
+                    depth[a]
+                }
+                Gate::RotateRight(a, _) | Gate::ShiftRight(a, _) | Gate::ShiftLeft(a, _) => {
+                    stats.wiring_gates += 1;
+                    depth[a]
+                }
+                Gate::Add(a, b) => {
+                    stats.add_gates += 1;
+                    depth[a].max(depth[b]) + ADDER_DEPTH
+                }
+            };
+        }
+        stats.and_depth = self.outputs.iter().map(|&w| depth[w]).max().unwrap_or(0);
+        stats
+    }
+
+    /// Runs the circuit gate by gate on shares of `input`.
+    pub fn eval<W: Word>(&self, input: &[W], input_pub: &[u32]) -> IKosResult<Vec<W>> {
+        if input.len() != self.input_len || input_pub.len() != self.input_pub_len {
+            return Err(IKosError {
+                error: String::from("circuit input length error"),
+            });
+        }
+        let mut wires: Vec<W> = Vec::with_capacity(self.gates.len());
+        for gate in &self.gates {
+            let value = match *gate {
+                Gate::Input(i) => input[i].clone(),
+                Gate::PublicInput(i) => W::constant(input_pub[i]),
+                Gate::Const(value) => W::constant(value),
+                Gate::Xor(a, b) => wires[a].clone().xor(&wires[b]),
+                Gate::And(a, b) => wires[a].clone().and(&wires[b])?,
+                Gate::AndConst(a, mask) => wires[a].clone().and_const(mask),
+                Gate::Not(a) => wires[a].clone().not(),
+                Gate::RotateRight(a, n) => wires[a].clone().rotate_right(n),
+                Gate::ShiftRight(a, n) => wires[a].clone().rshift(n),
+                Gate::ShiftLeft(a, n) => wires[a].clone().lshift(n),
+                Gate::Add(a, b) => wires[a].clone().add(&wires[b])?,
+            };
+            wires.push(value);
+        }
+        Ok(self.outputs.iter().map(|&w| wires[w].clone()).collect())
+    }
+}
+
+/// A word the circuit can be evaluated on: the prover's shares on either
+/// engine, or the verifier's two opened shares.
+pub trait Word: Clone {
+    fn constant(value: u32) -> Self;
+    fn xor(self, rhs: &Self) -> Self;
+    fn and_const(self, mask: u32) -> Self;
+    fn not(self) -> Self;
+    fn rotate_right(self, n: u32) -> Self;
+    fn rshift(self, n: u32) -> Self;
+    fn lshift(self, n: u32) -> Self;
+    fn and(self, rhs: &Self) -> IKosResult<Self>;
+    fn add(self, rhs: &Self) -> IKosResult<Self>;
+}
+
+impl<V: Variable4P> Word for V {
+    fn constant(value: u32) -> Self {
+        V::new_value(value)
+    }
+
+    fn xor(self, rhs: &Self) -> Self {
+        Variable4P::xor(self, rhs)
+    }
+
+    fn and_const(self, mask: u32) -> Self {
+        Variable4P::and_const(self, mask)
+    }
+
+    fn not(self) -> Self {
+        self.negate()
+    }
+
+    fn rotate_right(self, n: u32) -> Self {
+        Variable4P::rotate_right(self, n)
+    }
+
+    fn rshift(self, n: u32) -> Self {
+        Variable4P::rshift(self, n)
+    }
+
+    fn lshift(self, n: u32) -> Self {
+        Variable4P::lshift(self, n)
+    }
+
+    fn and(self, rhs: &Self) -> IKosResult<Self> {
+        Ok(self.bit_and(rhs))
+    }
+
+    fn add(self, rhs: &Self) -> IKosResult<Self> {
+        Ok(self.add_op(rhs))
+    }
+}
+
+impl Word for IKosVariable4V {
+    fn constant(value: u32) -> Self {
+        IKosVariable4V::new_value(value)
+    }
+
+    fn xor(self, rhs: &Self) -> Self {
+        IKosVariable4V::xor(self, rhs)
+    }
+
+    fn and_const(self, mask: u32) -> Self {
+        IKosVariable4V::and_const(self, mask)
+    }
+
+    fn not(self) -> Self {
+        self.negate()
+    }
+
+    fn rotate_right(self, n: u32) -> Self {
+        IKosVariable4V::rotate_right(self, n)
+    }
+
+    fn rshift(self, n: u32) -> Self {
+        IKosVariable4V::rshift(self, n)
+    }
+
+    fn lshift(self, n: u32) -> Self {
+        IKosVariable4V::lshift(self, n)
+    }
+
+    fn and(self, rhs: &Self) -> IKosResult<Self> {
+        self.bit_and(rhs)
+    }
+
+    fn add(self, rhs: &Self) -> IKosResult<Self> {
+        self.add_op(rhs)
+    }
+}
+
+// Prover gates never fail, they only read the unbounded random tapes; the
+// input lengths are the caller's to get right.
+impl Circuit4PTrait for Circuit {
+    fn circuit(&self, input: Vec<IKosVariable4P>, input_pub: &[u32]) -> Vec<IKosVariable4P> {
+        self.eval(&input, input_pub).unwrap()
+    }
+
+    fn circuit_bitsliced(&self, input: Vec<Bitsliced4P>, input_pub: &[u32]) -> Option<Vec<Bitsliced4P>> {
+        Some(self.eval(&input, input_pub).unwrap())
+    }
+
+    fn accepts(&self, output: &[u32]) -> bool {
+        Circuit::accepts(self, output)
+    }
+}
+
+impl Circuit4VTrait for Circuit {
+    fn circuit(&self, input: &[IKosVariable4V], input_pub: &[u32]) -> IKosResult<Vec<IKosVariable4V>> {
+        self.eval(input, input_pub)
+    }
+
+    fn accepts(&self, output: &[u32]) -> bool {
+        Circuit::accepts(self, output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::view_bytes;
+    use crate::zkboo::ikos::MasterSeeder;
+    use crate::zkboo::zkboo::{Engine, ProvingProof, VerifyingProof, ZkBoo};
+
+    // (a + b == c) ^ p, with `c` and `p` public.
+    fn sum_check() -> Circuit {
+        let mut circuit = Circuit::new();
+        let a = circuit.input();
+        let b = circuit.input();
+        let c = circuit.public_input();
+        let p = circuit.public_input();
+        let sum = circuit.add(a, b);
+        let eq = circuit.equal(sum, c);
+        let out = circuit.xor(eq, p);
+        circuit.set_outputs(vec![out, sum]);
+        circuit
+    }
+
+    #[test]
+    fn test_stats() {
+        let stats = sum_check().stats();
+        assert_eq!(stats.inputs, 2);
+        assert_eq!(stats.public_inputs, 2);
+        assert_eq!(stats.add_gates, 1);
+        assert_eq!(stats.and_gates, 5);
+        assert_eq!(stats.view_words(), 6);
+        assert_eq!(stats.and_depth, ADDER_DEPTH + 5);
+    }
+
+    #[test]
+    fn test_eval_on_constants() {
+        let circuit = sum_check();
+        let input = [IKosVariable4P::new_value(3), IKosVariable4P::new_value(4)];
+        let out = circuit.eval(&input, &[7, 1]).unwrap();
+        assert_eq!(out[0].value[0], u32::MAX - 1);
+        assert_eq!(out[1].value[0], 7);
+        let out = circuit.eval(&input, &[8, 1]).unwrap();
+        assert_eq!(out[0].value[0], 1);
+    }
+
+    fn prove(circuit: Circuit, input: Vec<u32>, input_pub: Vec<u32>) -> Vec<u32> {
+        let output_len = circuit.outputs().len();
+        let proving = ProvingProof::new(input, input_pub, output_len, Box::new(circuit));
+        ZkBoo::new(1, 3, 2, 32).prove(proving).unwrap().output
+    }
+
+    #[test]
+    fn test_gadgets() {
+        let mut circuit = Circuit::new();
+        let a = circuit.input();
+        let b = circuit.input();
+        let or = circuit.or(a, b);
+        let gt = circuit.gt(a, b);
+        let lt = circuit.gt(b, a);
+        let mask = circuit.constant(0xffff0000);
+        let mux = circuit.mux(mask, a, b);
+        circuit.set_outputs(vec![or, gt, lt, mux]);
+        let out = prove(circuit, vec![0x1234_5678, 0x0f0f_0f0f], vec![]);
+        assert_eq!(out, vec![0x1f3f_5f7f, u32::MAX, 0, 0x0f0f_5678]);
+    }
+
+    #[test]
+    fn test_barrel_shifts_match_u32() {
+        let mut circuit = Circuit::new();
+        let a = circuit.input();
+        let n = circuit.input();
+        let outputs = vec![
+            circuit.rshift_var(a, n),
+            circuit.lshift_var(a, n),
+            circuit.rotate_right_var(a, n),
+            circuit.rotate_left_var(a, n),
+        ];
+        circuit.set_outputs(outputs);
+        assert_eq!(circuit.stats().and_gates, 11 + 11 + 5 + 5);
+        for &a in [0x8000_0001, 0x1234_5678, u32::MAX].iter() {
+            for n in (0..40).chain([0x100, u32::MAX].iter().copied()) {
+                let input = vec![IKosVariable4P::new_value(a), IKosVariable4P::new_value(n)];
+                let out: Vec<_> = circuit.eval(&input, &[]).unwrap().iter().map(|v| v.value[0]).collect();
+                let expected = vec![
+                    a.checked_shr(n).unwrap_or(0),
+                    a.checked_shl(n).unwrap_or(0),
+                    a.rotate_right(n),
+                    a.rotate_left(n),
+                ];
+                assert_eq!(out, expected, "a = {:#x}, n = {}", a, n);
+            }
+        }
+        // Through a proof as well, where the selections are real AND gates.
+        assert_eq!(prove(circuit, vec![0x1234_5678, 12], vec![]), vec![0x12345, 0x4567_8000, 0x6781_2345, 0x4567_8123]);
+    }
+
+    #[test]
+    fn test_add_checked_flags_overflow() {
+        let mut circuit = Circuit::new();
+        let a = circuit.input();
+        let b = circuit.input();
+        let (sum, overflow) = circuit.add_checked(a, b);
+        circuit.set_outputs(vec![sum, overflow]);
+        let edges = [0, 1, 0x7fff_ffff, 0x8000_0000, 0xffff_fffe, u32::MAX];
+        for &a in edges.iter() {
+            for &b in edges.iter() {
+                let input = vec![IKosVariable4P::new_value(a), IKosVariable4P::new_value(b)];
+                let out: Vec<_> = circuit.eval(&input, &[]).unwrap().iter().map(|v| v.value[0]).collect();
+                let (sum, overflow) = a.overflowing_add(b);
+                assert_eq!(out, vec![sum, if overflow { u32::MAX } else { 0 }]);
+            }
+        }
+        assert_eq!(prove(circuit.clone(), vec![u32::MAX, 2], vec![]), vec![1, u32::MAX]);
+        assert_eq!(prove(circuit, vec![0x8000_0000, 0x7fff_ffff], vec![]), vec![u32::MAX, 0]);
+    }
+
+    #[test]
+    fn test_checked_sub_and_mul_match_u32() {
+        let mut circuit = Circuit::new();
+        let a = circuit.input();
+        let b = circuit.input();
+        let (diff, underflow) = circuit.sub_checked(a, b);
+        let (product, overflow) = circuit.mul_checked(a, b);
+        let wrapped_diff = circuit.sub(a, b);
+        let wrapped_product = circuit.mul(a, b);
+        circuit.set_outputs(vec![diff, underflow, product, overflow, wrapped_diff, wrapped_product]);
+        let mask = |flag| if flag { u32::MAX } else { 0 };
+        let edges = [0, 1, 2, 3, 0xffff, 0x1_0000, 0x7fff_ffff, 0x8000_0000, u32::MAX];
+        for &a in edges.iter() {
+            for &b in edges.iter() {
+                let input = vec![IKosVariable4P::new_value(a), IKosVariable4P::new_value(b)];
+                let out: Vec<_> = circuit.eval(&input, &[]).unwrap().iter().map(|v| v.value[0]).collect();
+                let (diff, underflow) = a.overflowing_sub(b);
+                let (product, overflow) = a.overflowing_mul(b);
+                assert_eq!(out, vec![diff, mask(underflow), product, mask(overflow), diff, product], "{:#x}, {:#x}", a, b);
+            }
+        }
+        let out = prove(circuit, vec![0x1_0001, 0xffff], vec![]);
+        assert_eq!(out, vec![2, 0, 0xffff_ffff, 0, 2, 0xffff_ffff]);
+    }
+
+    #[test]
+    fn test_error_word_decides_acceptance() {
+        let mut circuit = Circuit::new();
+        let a = circuit.input();
+        let b = circuit.input();
+        let (diff, underflow) = circuit.sub_checked(a, b);
+        circuit.set_outputs(vec![diff]);
+        circuit.set_error(underflow);
+        assert_eq!(circuit.error(), Some(underflow));
+        assert!(circuit.accepts(&[1, 0]));
+        assert!(!circuit.accepts(&[1, u32::MAX]));
+        assert!(!circuit.accepts(&[1]));
+
+        let zk_boo = ZkBoo::new(4, 3, 2, 32);
+        let proving = ProvingProof::new(vec![1, 2], vec![], 2, Box::new(circuit.clone()));
+        assert!(zk_boo.prove(proving).is_err());
+        let proving = ProvingProof::new(vec![2, 1], vec![], 2, Box::new(circuit.clone()));
+        let mut proof = zk_boo.prove(proving).unwrap();
+        assert_eq!(proof.output, vec![1, 0]);
+
+        // A proof claiming a failed check does not verify even if the
+        // views are consistent with it.
+        let challenge = proof.challenge();
+        let response = zk_boo.build_response(&proof, &challenge);
+        let two_views = zk_boo.rebuild_proof(&mut proof, &challenge);
+        let verifying = |output: Vec<u32>| {
+            VerifyingProof::new(proof.input_len, vec![], output, challenge, two_views.clone(), response.clone(), Box::new(circuit.clone()))
+        };
+        assert_eq!(zk_boo.verify(verifying(vec![1, 0])), Ok(true));
+        assert_eq!(zk_boo.verify(verifying(vec![1, u32::MAX])), Ok(false));
+    }
+
+    #[test]
+    fn test_comparisons_are_unsigned_and_strict() {
+        let mut circuit = Circuit::new();
+        let a = circuit.input();
+        let b = circuit.input();
+        let gt = circuit.gt(a, b);
+        circuit.set_outputs(vec![gt]);
+        assert_eq!(circuit.stats().and_gates, 10);
+        let values = [0, 1, 2, 0x7fff_ffff, 0x8000_0000, 0x8000_0001, 0xffff_fffe, u32::MAX];
+        for &a in values.iter() {
+            for &b in values.iter() {
+                let input = vec![IKosVariable4P::new_value(a), IKosVariable4P::new_value(b)];
+                let out = circuit.eval(&input, &[]).unwrap()[0].value[0];
+                assert_eq!(out, if a > b { u32::MAX } else { 0 }, "{:#x} > {:#x}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_in_range_vectors() {
+        let mut circuit = Circuit::new();
+        let x = circuit.input();
+        let lo = circuit.public_input();
+        let hi = circuit.public_input();
+        let inside = circuit.in_range(x, lo, hi);
+        circuit.set_outputs(vec![inside]);
+        assert_eq!(circuit.stats().and_gates, 21);
+
+        let zk_boo = ZkBoo::new(4, 3, 2, 32);
+        let vectors = include_str!("circuit/range_vectors.txt");
+        for line in vectors.lines().filter(|line| !line.starts_with('#')) {
+            let fields: Vec<_> = line.split_whitespace().map(|f| u32::from_str_radix(f, 16).unwrap()).collect();
+            let (x, bounds, expected) = (fields[0], vec![fields[1], fields[2]], fields[3] * u32::MAX);
+            for &engine in [Engine::PerRound, Engine::Bitsliced].iter() {
+                let proving = ProvingProof::new(vec![x], bounds.clone(), 1, Box::new(circuit.clone())).with_engine(engine);
+                let mut proof = zk_boo.prove(proving).unwrap();
+                assert_eq!(proof.output, vec![expected], "{}", line);
+
+                let challenge = proof.challenge();
+                let response = zk_boo.build_response(&proof, &challenge);
+                let two_views = zk_boo.rebuild_proof(&mut proof, &challenge);
+                let verifying = VerifyingProof::new(1, bounds.clone(), vec![expected], challenge, two_views, response, Box::new(circuit.clone()));
+                assert_eq!(zk_boo.verify(verifying), Ok(true), "{}", line);
+            }
+        }
+    }
+
+    #[test]
+    fn test_same_circuit_proves_and_verifies() {
+        let circuit = sum_check();
+        let zk_boo = ZkBoo::new(40, 3, 2, 32);
+        for &engine in [Engine::PerRound, Engine::Bitsliced].iter() {
+            let proving = ProvingProof::new(vec![3, 4], vec![7, 1], 2, Box::new(circuit.clone()))
+                .with_seeder(Box::new(MasterSeeder::new([1; 32])))
+                .with_engine(engine);
+            let mut proof = zk_boo.prove(proving).unwrap();
+            assert_eq!(proof.output, vec![u32::MAX - 1, 7]);
+
+            let challenge = proof.challenge();
+            let response = zk_boo.build_response(&proof, &challenge);
+            let two_views = zk_boo.rebuild_proof(&mut proof, &challenge);
+            let sent = response.iter().map(view_bytes).sum::<usize>()
+                + two_views.len()
+                + 32
+                + 4 * (1 + proof.input_pub.len() + proof.output.len());
+            let estimate = zk_boo.proof_size(&circuit);
+            assert!(sent <= estimate && estimate <= sent + 40 * 4 * proof.input_len);
+            let verifying = VerifyingProof::new(
+                proof.input_len,
+                proof.input_pub.clone(),
+                proof.output.clone(),
+                challenge,
+                two_views,
+                response,
+                Box::new(circuit.clone()),
+            );
+            assert!(zk_boo.verify(verifying).unwrap());
+        }
+    }
+}
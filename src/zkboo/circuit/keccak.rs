@@ -0,0 +1,177 @@
+use crate::zkboo::circuit::{Circuit, Wire};
+
+const ROUND_CONSTANTS: [u64; 24] = [
+    0x0000_0000_0000_0001, 0x0000_0000_0000_8082, 0x8000_0000_0000_808a, 0x8000_0000_8000_8000,
+    0x0000_0000_0000_808b, 0x0000_0000_8000_0001, 0x8000_0000_8000_8081, 0x8000_0000_0000_8009,
+    0x0000_0000_0000_008a, 0x0000_0000_0000_0088, 0x0000_0000_8000_8009, 0x0000_0000_8000_000a,
+    0x0000_0000_8000_808b, 0x8000_0000_0000_008b, 0x8000_0000_0000_8089, 0x8000_0000_0000_8003,
+    0x8000_0000_0000_8002, 0x8000_0000_0000_0080, 0x0000_0000_0000_800a, 0x8000_0000_8000_000a,
+    0x8000_0000_8000_8081, 0x8000_0000_0000_8080, 0x0000_0000_8000_0001, 0x8000_0000_8000_8008,
+];
+
+/// Rotation of lane `x + 5 * y` in the rho step.
+const ROTATIONS: [u32; 25] = [
+    0, 1, 62, 28, 27, 36, 44, 6, 55, 20, 3, 10, 43, 25, 39, 41, 45, 15, 21, 8, 18, 2, 61, 56, 14,
+];
+
+/// Rate of Keccak-256 in words: 136 bytes.
+const RATE_WORDS: usize = 34;
+
+/// A 64-bit lane as its low and high words.
+#[derive(Clone, Copy)]
+struct Lane {
+    lo: Wire,
+    hi: Wire,
+}
+
+impl Circuit {
+    /// Keccak-256 of `message` as Solidity's `keccak256` computes it, with
+    /// the original Keccak padding rather than SHA-3's. Words are taken as
+    /// four big-endian bytes each and the digest is eight words, a
+    /// `bytes32`.
+    ///
+    /// Lanes are kept as pairs of words, so a 64-bit rotation is two
+    /// shifts per word. The only AND gates are in the chi step, 50 per
+    /// round and 1200 per block.
+    ///
+    /// The emitted verifier spends about 8e6 gas per round on each block,
+    /// so at more than three rounds it exceeds `cost::BLOCK_GAS_LIMIT`
+    /// and the proof can only be checked off chain.
+    pub fn keccak256(&mut self, message: &[Wire]) -> Vec<Wire> {
+        let mut words = message.to_vec();
+        let pad = RATE_WORDS - words.len() % RATE_WORDS;
+        // 0x01 after the message and 0x80 in the last byte of the block.
+        for i in 0..pad {
+            let first = if i == 0 { 0x0100_0000 } else { 0 };
+            let last = if i == pad - 1 { 0x80 } else { 0 };
+            words.push(self.constant(first | last));
+        }
+
+        let zero = self.constant(0);
+        let mut state = [Lane { lo: zero, hi: zero }; 25];
+        for block in words.chunks(RATE_WORDS) {
+            for (lane, pair) in state.iter_mut().zip(block.chunks(2)) {
+                let (lo, hi) = (self.swap_bytes(pair[0]), self.swap_bytes(pair[1]));
+                lane.lo = self.xor(lane.lo, lo);
+                lane.hi = self.xor(lane.hi, hi);
+            }
+            for &rc in ROUND_CONSTANTS.iter() {
+                self.keccak_round(&mut state, rc);
+            }
+        }
+        let mut digest = vec![];
+        for lane in &state[..4] {
+            digest.push(self.swap_bytes(lane.lo));
+            digest.push(self.swap_bytes(lane.hi));
+        }
+        digest
+    }
+
+    fn keccak_round(&mut self, a: &mut [Lane; 25], rc: u64) {
+        // theta
+        let mut c = [a[0], a[1], a[2], a[3], a[4]];
+        for (x, column) in c.iter_mut().enumerate() {
+            for y in 1..5 {
+                *column = self.xor_lane(*column, a[x + 5 * y]);
+            }
+        }
+        for x in 0..5 {
+            let rotated = self.rotate_lane(c[(x + 1) % 5], 1);
+            let d = self.xor_lane(c[(x + 4) % 5], rotated);
+            for y in 0..5 {
+                a[x + 5 * y] = self.xor_lane(a[x + 5 * y], d);
+            }
+        }
+        // rho and pi
+        let mut b = *a;
+        for x in 0..5 {
+            for y in 0..5 {
+                b[y + 5 * ((2 * x + 3 * y) % 5)] = self.rotate_lane(a[x + 5 * y], ROTATIONS[x + 5 * y]);
+            }
+        }
+        // chi
+        for y in 0..5 {
+            for x in 0..5 {
+                let (b1, b2) = (b[(x + 1) % 5 + 5 * y], b[(x + 2) % 5 + 5 * y]);
+                let (not_lo, not_hi) = (self.not(b1.lo), self.not(b1.hi));
+                let and = Lane {
+                    lo: self.and(not_lo, b2.lo),
+                    hi: self.and(not_hi, b2.hi),
+                };
+                a[x + 5 * y] = self.xor_lane(b[x + 5 * y], and);
+            }
+        }
+        // iota
+        let rc = Lane {
+            lo: self.constant(rc as u32),
+            hi: self.constant((rc >> 32) as u32),
+        };
+        a[0] = self.xor_lane(a[0], rc);
+    }
+
+    fn xor_lane(&mut self, a: Lane, b: Lane) -> Lane {
+        Lane {
+            lo: self.xor(a.lo, b.lo),
+            hi: self.xor(a.hi, b.hi),
+        }
+    }
+
+    // The bits moving between the words do not overlap the shifted ones,
+    // so they are combined with XOR.
+    fn rotate_lane(&mut self, lane: Lane, n: u32) -> Lane {
+        let lane = if n >= 32 { Lane { lo: lane.hi, hi: lane.lo } } else { lane };
+        let n = n % 32;
+        if n == 0 {
+            return lane;
+        }
+        let (lo_up, lo_out) = (self.lshift(lane.lo, n), self.rshift(lane.lo, 32 - n));
+        let (hi_up, hi_out) = (self.lshift(lane.hi, n), self.rshift(lane.hi, 32 - n));
+        Lane {
+            lo: self.xor(lo_up, hi_out),
+            hi: self.xor(hi_up, lo_out),
+        }
+    }
+
+    // Keccak reads lanes little-endian, words are packed big-endian.
+    fn swap_bytes(&mut self, word: Wire) -> Wire {
+        let b0 = self.rshift(word, 24);
+        let b3 = self.lshift(word, 24);
+        let mid = self.rotate_right(word, 8);
+        let b1 = self.and_const(mid, 0x0000_ff00);
+        let mid = self.rotate_right(word, 24);
+        let b2 = self.and_const(mid, 0x00ff_0000);
+        let low = self.xor(b0, b1);
+        let high = self.xor(b2, b3);
+        self.xor(low, high)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zkboo::ikos::IKosVariable4P;
+    use crate::zkboo::utils::convert_u8_to_u32;
+    use crypto::digest::Digest;
+    use crypto::sha3::Sha3;
+
+    #[test]
+    fn test_keccak256_matches_rust_crypto() {
+        // 33 words leave room for the padding in one block, 34 need a
+        // second one.
+        for &len in [0, 1, 8, 33, 34, 35, 70].iter() {
+            let bytes: Vec<u8> = (0..4 * len).map(|i| (i * 13 + 5) as u8).collect();
+            let mut circuit = Circuit::new();
+            let message: Vec<_> = (0..len).map(|_| circuit.input()).collect();
+            let digest = circuit.keccak256(&message);
+            circuit.set_outputs(digest);
+
+            let input: Vec<_> = convert_u8_to_u32(&bytes, len).into_iter().map(IKosVariable4P::new_value).collect();
+            let out: Vec<_> = circuit.eval(&input, &[]).unwrap().iter().map(|v| v.value[0]).collect();
+            let mut keccak = Sha3::keccak256();
+            keccak.input(&bytes);
+            let mut expected = [0; 32];
+            keccak.result(&mut expected);
+            assert_eq!(out, convert_u8_to_u32(&expected, 8), "{} words", len);
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use crate::zkboo::circuit::{Circuit, Wire};
+
+/// Words of a node of the tree, a `bytes32`.
+pub const NODE_WORDS: usize = 8;
+
+impl Circuit {
+    /// All ones if `leaf` is in the Keccak-256 Merkle tree with `root`,
+    /// otherwise zero.
+    ///
+    /// `path` holds the sibling of each level from the leaf up, so the
+    /// tree has `path.len() / NODE_WORDS` levels, at most 32. Bit `i` of
+    /// `index` is set where the node at level `i` is a right child. A
+    /// parent is `keccak256(abi.encodePacked(left, right))`. The order of
+    /// each pair is chosen with masks, 8 AND gates per level, so neither
+    /// the leaf nor its position shows in the views.
+    ///
+    /// Every level hashes one Keccak-256 block, so even a single level is
+    /// over `cost::BLOCK_GAS_LIMIT` at the emitted rounds; see `keccak256`.
+    pub fn merkle_verify(&mut self, root: &[Wire], leaf: &[Wire], path: &[Wire], index: Wire) -> Wire {
+        let mut node = leaf.to_vec();
+        for (level, sibling) in path.chunks(NODE_WORDS).enumerate() {
+            let right = self.bit_mask(index, level as u32);
+            let mut pair = vec![];
+            let mut swapped = vec![];
+            for (&n, &s) in node.iter().zip(sibling) {
+                // Where `right` is set both words trade places.
+                let diff = self.xor(n, s);
+                let picked = self.and(right, diff);
+                pair.push(self.xor(n, picked));
+                swapped.push(self.xor(s, picked));
+            }
+            pair.extend(swapped);
+            node = self.keccak256(&pair);
+        }
+        let equal: Vec<_> = node.iter().zip(root).map(|(&a, &b)| self.equal(a, b)).collect();
+        equal.into_iter().reduce(|acc, eq| self.and(acc, eq)).unwrap_or_else(|| self.constant(u32::MAX))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zkboo::ikos::IKosVariable4P;
+    use crate::zkboo::utils::convert_u8_to_u32;
+    use crate::zkboo::zkboo::{ProvingProof, VerifyingProof, ZkBoo};
+    use crypto::digest::Digest;
+    use crypto::sha3::Sha3;
+
+    fn keccak(data: &[u8]) -> [u8; 32] {
+        let mut keccak = Sha3::keccak256();
+        keccak.input(data);
+        let mut digest = [0; 32];
+        keccak.result(&mut digest);
+        digest
+    }
+
+    // A tree of depth 3 over eight leaves, with the root and the siblings
+    // of every leaf.
+    fn tree() -> ([u8; 32], Vec<[u8; 32]>, Vec<Vec<u8>>) {
+        let leaves: Vec<[u8; 32]> = (0..8u8).map(|i| keccak(&[i])).collect();
+        let mut levels = vec![leaves.clone()];
+        while levels.last().unwrap().len() > 1 {
+            let parents = levels.last().unwrap().chunks(2).map(|pair| keccak(&[pair[0], pair[1]].concat())).collect();
+            levels.push(parents);
+        }
+        let paths = (0..8)
+            .map(|leaf| (0..3).flat_map(|level| levels[level][(leaf >> level) ^ 1].to_vec()).collect())
+            .collect();
+        (levels[3][0], leaves, paths)
+    }
+
+    fn circuit() -> Circuit {
+        let mut circuit = Circuit::new();
+        let leaf: Vec<_> = (0..8).map(|_| circuit.input()).collect();
+        let path: Vec<_> = (0..24).map(|_| circuit.input()).collect();
+        let index = circuit.input();
+        let root: Vec<_> = (0..8).map(|_| circuit.public_input()).collect();
+        let member = circuit.merkle_verify(&root, &leaf, &path, index);
+        circuit.set_outputs(vec![member]);
+        circuit
+    }
+
+    fn input(leaf: &[u8; 32], path: &[u8], index: u32) -> Vec<u32> {
+        let mut input = convert_u8_to_u32(leaf, 8);
+        input.extend(convert_u8_to_u32(path, 24));
+        input.push(index);
+        input
+    }
+
+    #[test]
+    fn test_every_leaf_is_a_member() {
+        let (root, leaves, paths) = tree();
+        let circuit = circuit();
+        let root = convert_u8_to_u32(&root, 8);
+        let eval = |input: Vec<u32>| {
+            let input: Vec<_> = input.into_iter().map(IKosVariable4P::new_value).collect();
+            circuit.eval(&input, &root).unwrap()[0].value[0]
+        };
+        for i in 0..8 {
+            assert_eq!(eval(input(&leaves[i], &paths[i], i as u32)), u32::MAX, "leaf {}", i);
+            // The wrong position or another leaf's path do not verify.
+            assert_eq!(eval(input(&leaves[i], &paths[i], i as u32 ^ 1)), 0);
+            assert_eq!(eval(input(&leaves[i], &paths[(i + 1) % 8], i as u32)), 0);
+        }
+        assert_eq!(eval(input(&keccak(b"x"), &paths[0], 0)), 0);
+    }
+
+    #[test]
+    fn test_membership_proof_verifies() {
+        let (root, leaves, paths) = tree();
+        let circuit = circuit();
+        let root = convert_u8_to_u32(&root, 8);
+        let zk_boo = ZkBoo::new(2, 3, 2, 32);
+        let proving = ProvingProof::new(input(&leaves[5], &paths[5], 5), root.clone(), 1, Box::new(circuit.clone()));
+        let mut proof = zk_boo.prove(proving).unwrap();
+        assert_eq!(proof.output, vec![u32::MAX]);
+        let challenge = proof.challenge();
+        let response = zk_boo.build_response(&proof, &challenge);
+        let two_views = zk_boo.rebuild_proof(&mut proof, &challenge);
+        let verifying = VerifyingProof::new(proof.input_len, root, proof.output.clone(), challenge, two_views, response, Box::new(circuit));
+        assert_eq!(zk_boo.verify(verifying), Ok(true));
+    }
+}
@@ -0,0 +1,161 @@
+use crate::zkboo::circuit::{Circuit, Wire};
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const H0: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+impl Circuit {
+    /// SHA-256 of `message`, whose words are taken as four big-endian
+    /// bytes each, as `bytesN` values are packed. The digest is eight
+    /// words, a `bytes32`.
+    ///
+    /// The length is known when the circuit is built, so the padding is
+    /// constant. Per block, each of the 64 rounds costs two AND gates,
+    /// for `Ch` and `Maj`, and seven additions, and the message schedule
+    /// three additions per expanded word.
+    ///
+    /// Each addition walks the carry bit by bit in the Solidity verifier,
+    /// so a block costs about 1.8e7 gas per round and a proof of more than
+    /// one round is over `cost::BLOCK_GAS_LIMIT`.
+    pub fn sha256(&mut self, message: &[Wire]) -> Vec<Wire> {
+        let mut words = message.to_vec();
+        words.push(self.constant(0x8000_0000));
+        while words.len() % 16 != 14 {
+            words.push(self.constant(0));
+        }
+        let bits = 32 * message.len() as u64;
+        words.push(self.constant((bits >> 32) as u32));
+        words.push(self.constant(bits as u32));
+
+        let mut state: Vec<Wire> = H0.iter().map(|&h| self.constant(h)).collect();
+        for block in words.chunks(16) {
+            state = self.sha256_block(&state, block);
+        }
+        state
+    }
+
+    fn sha256_block(&mut self, state: &[Wire], block: &[Wire]) -> Vec<Wire> {
+        let mut w = block.to_vec();
+        for t in 16..64 {
+            let s0 = self.sigma(w[t - 15], 7, 18, 3, false);
+            let s1 = self.sigma(w[t - 2], 17, 19, 10, false);
+            let sum = self.add(w[t - 16], s0);
+            let sum = self.add(sum, w[t - 7]);
+            w.push(self.add(sum, s1));
+        }
+
+        let (mut a, mut b, mut c, mut d) = (state[0], state[1], state[2], state[3]);
+        let (mut e, mut f, mut g, mut h) = (state[4], state[5], state[6], state[7]);
+        for t in 0..64 {
+            let s1 = self.sigma(e, 6, 11, 25, true);
+            // Ch(e, f, g) = g ^ (e & (f ^ g))
+            let fg = self.xor(f, g);
+            let picked = self.and(e, fg);
+            let ch = self.xor(g, picked);
+            let k = self.constant(K[t]);
+            let t1 = self.add(h, s1);
+            let t1 = self.add(t1, ch);
+            let t1 = self.add(t1, k);
+            let t1 = self.add(t1, w[t]);
+
+            let s0 = self.sigma(a, 2, 13, 22, true);
+            // Maj(a, b, c) = a ^ ((a ^ b) & (a ^ c))
+            let (ab, ac) = (self.xor(a, b), self.xor(a, c));
+            let both = self.and(ab, ac);
+            let maj = self.xor(a, both);
+            let t2 = self.add(s0, maj);
+
+            h = g;
+            g = f;
+            f = e;
+            e = self.add(d, t1);
+            d = c;
+            c = b;
+            b = a;
+            a = self.add(t1, t2);
+        }
+        let out = [a, b, c, d, e, f, g, h];
+        state.iter().zip(out.iter()).map(|(&s, &x)| self.add(s, x)).collect()
+    }
+
+    // `rotr(x, r1) ^ rotr(x, r2) ^ rotr(x, r3)`, or with `x >> r3` as the
+    // last term for the message schedule.
+    fn sigma(&mut self, x: Wire, r1: u32, r2: u32, r3: u32, rotate: bool) -> Wire {
+        let a = self.rotate_right(x, r1);
+        let b = self.rotate_right(x, r2);
+        let c = if rotate { self.rotate_right(x, r3) } else { self.rshift(x, r3) };
+        let ab = self.xor(a, b);
+        self.xor(ab, c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zkboo::ikos::IKosVariable4P;
+    use crate::zkboo::utils::convert_u8_to_u32;
+    use crate::zkboo::zkboo::{ProvingProof, VerifyingProof, ZkBoo};
+    use crypto::digest::Digest;
+    use crypto::sha2::Sha256;
+
+    #[test]
+    fn test_sha256_matches_rust_crypto() {
+        // Up to 13 words the padding fits in one block, from 14 on it
+        // needs a second one.
+        for &len in [0, 1, 8, 13, 14, 16, 30].iter() {
+            let bytes: Vec<u8> = (0..4 * len).map(|i| (i * 7 + 3) as u8).collect();
+            let mut circuit = Circuit::new();
+            let message: Vec<_> = (0..len).map(|_| circuit.input()).collect();
+            let digest = circuit.sha256(&message);
+            circuit.set_outputs(digest);
+
+            let input: Vec<_> = convert_u8_to_u32(&bytes, len).into_iter().map(IKosVariable4P::new_value).collect();
+            let out: Vec<_> = circuit.eval(&input, &[]).unwrap().iter().map(|v| v.value[0]).collect();
+            let mut sha = Sha256::new();
+            sha.input(&bytes);
+            let mut expected = [0; 32];
+            sha.result(&mut expected);
+            assert_eq!(out, convert_u8_to_u32(&expected, 8), "{} words", len);
+        }
+    }
+
+    #[test]
+    fn test_preimage_proof_verifies() {
+        // sha256(secret) == digest with a public digest.
+        let mut circuit = Circuit::new();
+        let secret: Vec<_> = (0..8).map(|_| circuit.input()).collect();
+        let digest: Vec<_> = (0..8).map(|_| circuit.public_input()).collect();
+        let hash = circuit.sha256(&secret);
+        let diff: Vec<_> = hash.iter().zip(digest).map(|(&a, b)| circuit.xor(a, b)).collect();
+        let any = diff.into_iter().reduce(|a, b| circuit.or(a, b)).unwrap();
+        circuit.set_outputs(vec![any]);
+
+        let secret = [7u8; 32];
+        let mut sha = Sha256::new();
+        sha.input(&secret);
+        let mut digest = [0; 32];
+        sha.result(&mut digest);
+        let (secret, digest) = (convert_u8_to_u32(&secret, 8), convert_u8_to_u32(&digest, 8));
+
+        let zk_boo = ZkBoo::new(2, 3, 2, 32);
+        let proving = ProvingProof::new(secret, digest.clone(), 1, Box::new(circuit.clone()));
+        let mut proof = zk_boo.prove(proving).unwrap();
+        assert_eq!(proof.output, vec![0]);
+        let challenge = proof.challenge();
+        let response = zk_boo.build_response(&proof, &challenge);
+        let two_views = zk_boo.rebuild_proof(&mut proof, &challenge);
+        let verifying = VerifyingProof::new(proof.input_len, digest, proof.output.clone(), challenge, two_views, response, Box::new(circuit));
+        assert_eq!(zk_boo.verify(verifying), Ok(true));
+    }
+}
@@ -0,0 +1,124 @@
+use crate::zkboo::circuit::Circuit;
+use crate::zkboo::zkboo::ZkBoo;
+
+/// Gas the Solidity verifier in `traverser/zkboo.sol` spends per gate and
+/// per round, counted by hand from the template: each random word costs a
+/// share of one `sha256` block, the adder walks 31 bits on two parties, and
+/// views are copied into commitments byte by byte. The figures are meant
+/// to compare circuits and round counts, not to predict a transaction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GasModel {
+    /// `bit_and`: two `get_next_random_from_context` draws and a view read.
+    pub and_gate: u64,
+    /// `add_op`: the `get_bit` carry loop over 31 bits for both parties.
+    pub add_gate: u64,
+    /// `bit_xor`, `negate`, `rotr`, `rshift`, `lshift` and `and_const`.
+    pub local_gate: u64,
+    /// `IKosVariable_new_value` for a constant or a public input.
+    pub constant: u64,
+    /// The round loop of `ZKBoo_verify`: `new_views` for both opened
+    /// parties and the `_3DVector` bookkeeping.
+    pub round: u64,
+    /// One tape draw per opened party in `ZKBoo_verify`'s input shares.
+    pub input_word: u64,
+    /// A `write_uint32` byte in `commit_ikos_context`, or a byte copied
+    /// into `three_views` for `query_random_oracle`.
+    pub hashed_byte: u64,
+    /// Calldata of `VerifyingProof`, as priced by the EVM.
+    pub calldata_byte: u64,
+}
+
+impl Default for GasModel {
+    fn default() -> Self {
+        GasModel {
+            and_gate: 2_000,
+            add_gate: 28_000,
+            local_gate: 500,
+            constant: 300,
+            round: 10_000,
+            input_word: 1_200,
+            hashed_byte: 150,
+            calldata_byte: 16,
+        }
+    }
+}
+
+/// Gas limit of an Ethereum mainnet block. No transaction can spend more,
+/// so a verifier estimated above it cannot run on chain at all. The hash
+/// gadgets are far above it at the emitted 137 rounds: one Keccak-256
+/// block is about 1.1e9 gas, one SHA-256 block about 2.5e9, and a Merkle
+/// proof one Keccak-256 block per level.
+pub const BLOCK_GAS_LIMIT: u64 = 30_000_000;
+
+/// What proving and verifying `circuit` costs with the rounds of `zk_boo`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CostEstimate {
+    /// Upper bound, see `ZkBoo::proof_size`.
+    pub proof_bytes: usize,
+    pub verify_gas: u64,
+}
+
+impl CostEstimate {
+    /// Whether the verifier fits in a block, see `BLOCK_GAS_LIMIT`.
+    pub fn fits_in_block(&self) -> bool {
+        self.verify_gas <= BLOCK_GAS_LIMIT
+    }
+}
+
+impl GasModel {
+    pub fn estimate(&self, zk_boo: &ZkBoo, circuit: &Circuit) -> CostEstimate {
+        let stats = circuit.stats();
+        let proof_bytes = zk_boo.proof_size(circuit);
+        let (inputs, view_words) = (stats.inputs as u64, stats.view_words() as u64);
+
+        // Both opened views are committed to: label, blinding, seed, and
+        // the input and output words with their lengths.
+        let committed = 2 * (12 + 32 + 8 + 4 * (inputs + view_words + stats.outputs as u64));
+        let per_round = self.round
+            + self.and_gate * stats.and_gates as u64
+            + self.add_gate * stats.add_gates as u64
+            + self.local_gate * stats.local_gates() as u64
+            + self.constant * (stats.constants + stats.public_inputs) as u64
+            + self.input_word * 2 * inputs
+            + self.hashed_byte * committed;
+        let rounds = zk_boo.num_of_round() as u64;
+        // The challenge hashes three commitments and three output shares
+        // per round.
+        let challenge = self.hashed_byte * rounds * (3 * 32 + 3 * 4 * stats.outputs as u64);
+        CostEstimate {
+            proof_bytes,
+            verify_gas: rounds * per_round + challenge + self.calldata_byte * proof_bytes as u64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn and_chain(ands: usize) -> Circuit {
+        let mut circuit = Circuit::new();
+        let a = circuit.input();
+        let mut out = a;
+        for i in 0..ands {
+            let rotated = circuit.rotate_right(out, i as u32 + 1);
+            out = circuit.and(out, rotated);
+        }
+        circuit.set_outputs(vec![out]);
+        circuit
+    }
+
+    #[test]
+    fn test_estimate_grows_with_gates_and_rounds() {
+        let model = GasModel::default();
+        let small = model.estimate(&ZkBoo::new(10, 3, 2, 32), &and_chain(1));
+        let large = model.estimate(&ZkBoo::new(10, 3, 2, 32), &and_chain(11));
+        // Ten more view words in every round.
+        assert_eq!(large.proof_bytes - small.proof_bytes, 10 * 10 * 4);
+        assert!(large.verify_gas > small.verify_gas + 10 * 10 * model.and_gate);
+
+        let more_rounds = model.estimate(&ZkBoo::new(20, 3, 2, 32), &and_chain(1));
+        assert!(more_rounds.proof_bytes > 2 * small.proof_bytes - 100);
+        assert!(more_rounds.verify_gas > 2 * small.verify_gas - model.calldata_byte * 100);
+    }
+}
@@ -2,9 +2,9 @@ use crypto::digest::Digest;
 use crypto::sha2::Sha256;
 use hex::FromHex;
 use rand::Rng;
-use std::cell::RefCell;
-use std::rc::Rc;
-use crate::zkboo::utils::convert_u32_to_u8;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex, MutexGuard};
+use crate::zkboo::utils::{convert_u32_to_u8, convert_usize_to_u8};
 
 macro_rules! get_bit {
     ($x: expr, $i: expr) => {{
@@ -24,6 +24,11 @@ macro_rules! set_bit {
 
 pub type IKosResult<T> = Result<T, IKosError>;
 
+/// Length in bytes of each party's random tape seed.
+pub const RAND_TAPE_SEED_LEN: usize = 16;
+/// Length in bytes of the blinding value of each view commitment.
+pub const BLINDING_LEN: usize = 16;
+
 #[derive(Debug, PartialEq)]
 pub struct IKosError {
     pub error: String,
@@ -34,26 +39,35 @@ pub struct IKosView {
     pub rand_tape_seed: Vec<u8>,
     pub in_data: Vec<u32>,
     pub out_data: Vec<u32>,
+    /// Commitment randomness, opened together with the view
+    pub blinding: Vec<u8>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct IKosContext {
     pub ikos_view: IKosView,
     randomness: Vec<u32>,
+    rand_block_ctr: u32,
     pub used_rand_ctr: usize,
     pub out_view_ctr: usize,
 }
 
+/// The contexts of all parties of one round, shared by every variable of
+/// that round. Each round owns its own contexts, so rounds can run on
+/// different threads.
+#[derive(Clone, Debug, Default)]
+pub struct SharedContexts(Arc<Mutex<Vec<IKosContext>>>);
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct IKosVariable4P {
     pub value: Vec<u32>,
-    ctx: Rc<RefCell<Vec<IKosContext>>>,
+    ctx: SharedContexts,
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct IKosVariable4V {
     pub value: Vec<u32>,
-    pub ctx: Rc<RefCell<Vec<IKosContext>>>,
+    pub ctx: SharedContexts,
 }
 
 fn generate_random(num: usize) -> Vec<u8> {
@@ -65,10 +79,93 @@ fn generate_random(num: usize) -> Vec<u8> {
     randoms
 }
 
-fn generate_randomness(key: &[u8]) -> Vec<u32> {
+/// Length in bytes of a round seed.
+pub const ROUND_SEED_LEN: usize = 32;
+
+/// Supplies one seed per round of a proof. The random tape seed and the
+/// commitment blinding value of every party are derived from it with
+/// `party_seeds`.
+///
+/// All prover randomness is derived from these values, so a deterministic
+/// seeder makes the whole proof reproducible.
+pub trait TapeSeeder {
+    fn round_seed(&mut self, round: usize) -> [u8; ROUND_SEED_LEN];
+}
+
+/// Fresh seeds from the thread-local CSPRNG, which is seeded by the OS.
+pub struct OsSeeder;
+
+impl TapeSeeder for OsSeeder {
+    fn round_seed(&mut self, _round: usize) -> [u8; ROUND_SEED_LEN] {
+        let mut seed = [0; ROUND_SEED_LEN];
+        seed.copy_from_slice(&generate_random(ROUND_SEED_LEN));
+        seed
+    }
+}
+
+/// Round seeds expanded from a single master seed as
+/// `SHA-256("zoker.round_seed" || master || round)`. The same master seed
+/// replays the same proof.
+pub struct MasterSeeder {
+    master: [u8; 32],
+}
+
+impl MasterSeeder {
+    pub fn new(master: [u8; 32]) -> Self {
+        MasterSeeder { master }
+    }
+}
+
+impl TapeSeeder for MasterSeeder {
+    fn round_seed(&mut self, round: usize) -> [u8; ROUND_SEED_LEN] {
+        expand_seed(b"zoker.round_seed", &self.master, round)
+    }
+}
+
+fn expand_seed(label: &[u8], seed: &[u8], index: usize) -> [u8; 32] {
+    let mut sha = Sha256::new();
+    let mut vec = label.to_vec();
+    vec.extend(seed);
+    vec.extend(convert_usize_to_u8(index));
+    sha.input(&vec);
+    <[u8; 32]>::from_hex(sha.result_str()).unwrap()
+}
+
+/// Random tape seed and blinding value of `party`, derived from its round
+/// seed as `SHA-256(label || round_seed || party)` truncated to length, with
+/// `"zoker.tape_seed"` and `"zoker.blinding"` as labels.
+pub fn party_seeds(round_seed: &[u8; ROUND_SEED_LEN], party: usize) -> (Vec<u8>, Vec<u8>) {
+    let tape_seed = expand_seed(b"zoker.tape_seed", round_seed, party);
+    let blinding = expand_seed(b"zoker.blinding", round_seed, party);
+    (tape_seed[..RAND_TAPE_SEED_LEN].to_vec(), blinding[..BLINDING_LEN].to_vec())
+}
+
+impl SharedContexts {
+    pub fn new(contexts: Vec<IKosContext>) -> Self {
+        SharedContexts(Arc::new(Mutex::new(contexts)))
+    }
+
+    /// Locks the contexts. Never hold the guard across an operation on a
+    /// variable of the same round.
+    pub fn lock(&self) -> MutexGuard<'_, Vec<IKosContext>> {
+        self.0.lock().unwrap()
+    }
+}
+
+impl PartialEq for SharedContexts {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0) || *self.lock() == *other.lock()
+    }
+}
+
+/// Block `counter` of a party's random tape: `SHA-256(key || counter)` with
+/// a 4-byte big-endian counter, read as eight big-endian words.
+fn generate_randomness(key: &[u8], counter: u32) -> Vec<u32> {
     let mut randoms = vec![];
     let mut sha = Sha256::new();
-    sha.input(key);
+    let mut vec = key.to_vec();
+    vec.extend(convert_u32_to_u8(&[counter]));
+    sha.input(&vec);
     let hash = <[u8; 32]>::from_hex(sha.result_str()).unwrap();
     for i in 0..8 {
         let random = (hash[4 * i] as u32) << 24
@@ -82,7 +179,10 @@ fn generate_randomness(key: &[u8]) -> Vec<u32> {
 
 pub fn get_next_random_from_context(ctx: &mut IKosContext) -> IKosResult<u32> {
     if ctx.randomness.len() <= ctx.used_rand_ctr {
-        ctx.randomness = generate_randomness(convert_u32_to_u8(ctx.randomness.as_ref()).as_ref());
+        ctx.rand_block_ctr = ctx.rand_block_ctr.checked_add(1).ok_or_else(|| IKosError {
+            error: String::from("random tape exhausted"),
+        })?;
+        ctx.randomness = generate_randomness(&ctx.ikos_view.rand_tape_seed, ctx.rand_block_ctr);
         ctx.used_rand_ctr = 0;
     }
     let rand = ctx.randomness[ctx.used_rand_ctr as usize];
@@ -98,44 +198,112 @@ impl Default for IKosView {
 
 impl IKosView {
     pub fn new() -> Self {
-        let ozkb_rand_tape_seed_len = 16;
+        Self::new_with_seed(generate_random(RAND_TAPE_SEED_LEN), generate_random(BLINDING_LEN))
+    }
+
+    pub fn new_with_seed(rand_tape_seed: Vec<u8>, blinding: Vec<u8>) -> Self {
         IKosView {
-            rand_tape_seed: generate_random(ozkb_rand_tape_seed_len),
+            rand_tape_seed,
             in_data: vec![],
             out_data: vec![],
+            blinding,
         }
     }
 }
 
 impl IKosContext {
     pub fn new() -> Self {
-        let ikos_view = IKosView::new();
-        let seed = ikos_view.rand_tape_seed.clone();
-        IKosContext {
-            ikos_view,
-            randomness: generate_randomness(&seed),
-            used_rand_ctr: 0,
-            out_view_ctr: 0,
-        }
+        Self::new_views(IKosView::new())
+    }
+
+    pub fn new_with_seed(rand_tape_seed: Vec<u8>, blinding: Vec<u8>) -> Self {
+        Self::new_views(IKosView::new_with_seed(rand_tape_seed, blinding))
     }
 
     pub fn new_views(ikos_view: IKosView) -> Self {
         let seed = ikos_view.rand_tape_seed.clone();
         IKosContext {
             ikos_view,
-            randomness: generate_randomness(&seed),
+            randomness: generate_randomness(&seed, 0),
+            rand_block_ctr: 0,
             used_rand_ctr: 0,
             out_view_ctr: 0,
         }
     }
 
+    /// Commits to the whole view as
+    /// `SHA-256("zoker.commit" || blinding || rand_tape_seed || len(in_data)
+    /// || in_data || len(out_data) || out_data)`, lengths as 4-byte words.
     pub fn commit_ikos_context(&mut self) -> [u8; 32] {
+        let view = &self.ikos_view;
         let mut sha = Sha256::new();
-        let mut vec = self.ikos_view.rand_tape_seed.clone();
-        vec.extend(convert_u32_to_u8(&*self.ikos_view.out_data.clone()));
+        let mut vec = b"zoker.commit".to_vec();
+        vec.extend(&view.blinding);
+        vec.extend(&view.rand_tape_seed);
+        vec.extend(convert_usize_to_u8(view.in_data.len()));
+        vec.extend(convert_u32_to_u8(&view.in_data));
+        vec.extend(convert_usize_to_u8(view.out_data.len()));
+        vec.extend(convert_u32_to_u8(&view.out_data));
         sha.input(&vec);
-        let res = <[u8; 32]>::from_hex(sha.result_str()).unwrap();
-        res
+        <[u8; 32]>::from_hex(sha.result_str()).unwrap()
+    }
+}
+
+/// A secret-shared 32-bit word of the three-party prover.
+///
+/// Circuits written against this trait run on either evaluation engine:
+/// `IKosVariable4P` evaluates one round at a time, `Bitsliced4P` evaluates
+/// up to 32 rounds at once. Both consume the random tapes in the same order
+/// and so produce the same views.
+pub trait Variable4P: Clone + Debug + Send + Sync {
+    fn new_value(value: u32) -> Self;
+    fn negate(self) -> Self;
+    fn xor(self, rhs: &Self) -> Self;
+    fn rotate_right(self, n: u32) -> Self;
+    fn rshift(self, n: u32) -> Self;
+    fn lshift(self, n: u32) -> Self;
+    fn bit_and(self, rhs: &Self) -> Self;
+    /// AND with a public constant, which each party applies to its own
+    /// share.
+    fn and_const(self, mask: u32) -> Self;
+    fn add_op(self, rhs: &Self) -> Self;
+}
+
+impl Variable4P for IKosVariable4P {
+    fn new_value(value: u32) -> Self {
+        IKosVariable4P::new_value(value)
+    }
+
+    fn negate(self) -> Self {
+        IKosVariable4P::negate(self)
+    }
+
+    fn xor(self, rhs: &Self) -> Self {
+        IKosVariable4P::xor(self, rhs)
+    }
+
+    fn rotate_right(self, n: u32) -> Self {
+        IKosVariable4P::rotate_right(self, n)
+    }
+
+    fn rshift(self, n: u32) -> Self {
+        IKosVariable4P::rshift(self, n)
+    }
+
+    fn lshift(self, n: u32) -> Self {
+        IKosVariable4P::lshift(self, n)
+    }
+
+    fn bit_and(self, rhs: &Self) -> Self {
+        IKosVariable4P::bit_and(self, rhs)
+    }
+
+    fn and_const(self, mask: u32) -> Self {
+        IKosVariable4P::and_const(self, mask)
+    }
+
+    fn add_op(self, rhs: &Self) -> Self {
+        IKosVariable4P::add_op(self, rhs)
     }
 }
 
@@ -143,16 +311,16 @@ impl IKosVariable4P {
     pub fn new_value(value: u32) -> Self {
         IKosVariable4P {
             value: vec![value; 3],
-            ctx: Rc::new(RefCell::new(vec![])),
+            ctx: SharedContexts::default(),
         }
     }
 
-    pub fn new_share(value: Vec<u32>, ctx: Rc<RefCell<Vec<IKosContext>>>) -> Self {
+    pub fn new_share(value: Vec<u32>, ctx: SharedContexts) -> Self {
         IKosVariable4P { value, ctx }
     }
 
     fn is_empty_context(&self) -> bool {
-        self.ctx.borrow().is_empty()
+        self.ctx.lock().is_empty()
     }
 
     pub fn negate(mut self) -> Self {
@@ -163,12 +331,22 @@ impl IKosVariable4P {
     }
 
     pub fn xor(mut self, rhs: &IKosVariable4P) -> Self {
+        if self.is_empty_context() {
+            self.ctx = rhs.ctx.clone();
+        }
         for i in 0..3 {
             self.value[i] ^= rhs.value[i];
         }
         self
     }
 
+    pub fn rotate_right(mut self, n: u32) -> Self {
+        for i in 0..3 {
+            self.value[i] = self.value[i].rotate_right(n);
+        }
+        self
+    }
+
     pub fn rshift(mut self, n: u32) -> Self {
         for i in 0..3 {
             self.value[i] >>= n;
@@ -199,7 +377,7 @@ impl IKosVariable4P {
         }
 
         for (i, random) in rand.iter_mut().enumerate().take(3) {
-            *random = get_next_random_from_context(&mut self.ctx.borrow_mut()[i]).unwrap();
+            *random = get_next_random_from_context(&mut self.ctx.lock()[i]).unwrap();
         }
         for i in 0..3 {
             out[i] = (self.value[i] & rhs.value[(i + 1) % 3])
@@ -211,7 +389,7 @@ impl IKosVariable4P {
 
         for (i, &out_value) in out.iter().enumerate().take(3) {
             self.value[i] = out_value;
-            self.ctx.borrow_mut()[i]
+            self.ctx.lock()[i]
                 .ikos_view
                 .out_data
                 .push(self.value[i]);
@@ -219,32 +397,30 @@ impl IKosVariable4P {
         self
     }
 
-    pub fn bit_or(mut self, rhs: &IKosVariable4P) -> Self {
+    pub fn and_const(mut self, mask: u32) -> Self {
         for i in 0..3 {
-            self.value[i] |= rhs.value[i];
+            self.value[i] &= mask;
         }
         self
     }
 
-    pub fn gt(mut self, rhs: &IKosVariable4P) -> Self {
-        let minus_rhs = rhs.clone().negate().add_op(&IKosVariable4P::new_value(1));
-        let mut minus_gt = self.add_op(&minus_rhs);
+    pub fn bit_or(mut self, rhs: &IKosVariable4P) -> Self {
+        if self.is_empty_context() {
+            self.ctx = rhs.ctx.clone();
+        }
         for i in 0..3 {
-            let val = minus_gt.value[i] as u32;
-            let lsb = val >> 31;
-            if lsb == 0 {
-                minus_gt.value[i] = u32::MAX;
-            } else {
-                minus_gt.value[i] = 0;
-            }
+            self.value[i] |= rhs.value[i];
         }
-        minus_gt
+        self
     }
 
     pub fn if_op(mut self, rhs: &IKosVariable4P) -> Self {
         self.bit_and(rhs)
     }
 
+    /// Sum modulo 2^32. The ripple carry computes the carries into bits 1
+    /// to 31, which is every bit of the sum; the carry out of bit 31 is
+    /// dropped.
     pub fn add_op(mut self, rhs: &IKosVariable4P) -> Self {
         let mut a = vec![0; 3];
         let mut b = vec![0; 3];
@@ -252,9 +428,9 @@ impl IKosVariable4P {
         let mut out = vec![0; 3];
 
         if self.is_empty_context() && rhs.is_empty_context() {
-            // constant calculation
+            // constant calculation, modulo 2^32 like the shared one
             for i in 0..3 {
-                self.value[i] += rhs.value[i];
+                self.value[i] = self.value[i].wrapping_add(rhs.value[i]);
             }
             return self;
         }
@@ -263,7 +439,7 @@ impl IKosVariable4P {
             return rhs.clone().add_op(&self);
         }
         for (i, random) in rand.iter_mut().enumerate().take(3) {
-            *random = get_next_random_from_context(&mut self.ctx.borrow_mut()[i]).unwrap();
+            *random = get_next_random_from_context(&mut self.ctx.lock()[i]).unwrap();
         }
 
         for i in 0..31 {
@@ -285,7 +461,7 @@ impl IKosVariable4P {
 
         for (i, &out_value) in out.iter().enumerate().take(3) {
             self.value[i] = self.value[i] ^ rhs.value[i] ^ out_value;
-            self.ctx.borrow_mut()[i].ikos_view.out_data.push(out_value);
+            self.ctx.lock()[i].ikos_view.out_data.push(out_value);
         }
         self
     }
@@ -295,17 +471,17 @@ impl IKosVariable4V {
     pub fn new_value(value: u32) -> Self {
         IKosVariable4V {
             value: vec![value; 2],
-            ctx: Rc::new(RefCell::new(vec![])),
+            ctx: SharedContexts::default(),
         }
     }
 
-    pub fn new_share(value: Vec<u32>, ctx: Rc<RefCell<Vec<IKosContext>>>) -> Self {
+    pub fn new_share(value: Vec<u32>, ctx: SharedContexts) -> Self {
         IKosVariable4V { value, ctx }
     }
 
     // TODO: 이 함수들은 P 에서도 사용함
     fn is_empty_context(&self) -> bool {
-        self.ctx.borrow().is_empty()
+        self.ctx.lock().is_empty()
     }
 
     pub fn negate(mut self) -> Self {
@@ -316,12 +492,22 @@ impl IKosVariable4V {
     }
 
     pub fn xor(mut self, rhs: &IKosVariable4V) -> Self {
+        if self.is_empty_context() {
+            self.ctx = rhs.ctx.clone();
+        }
         for i in 0..2 {
             self.value[i] ^= rhs.value[i];
         }
         self
     }
 
+    pub fn rotate_right(mut self, n: u32) -> Self {
+        for i in 0..2 {
+            self.value[i] = self.value[i].rotate_right(n);
+        }
+        self
+    }
+
     pub fn rshift(mut self, n: u32) -> Self {
         for i in 0..2 {
             self.value[i] >>= n;
@@ -336,8 +522,13 @@ impl IKosVariable4V {
         self
     }
 
-    pub fn require_reconstruct(ctx: &[IKosContext]) -> bool {
-        ctx[0].ikos_view.out_data.len() != ctx[1].ikos_view.out_data.len()
+    // Next gate output of the second opened party, the only one the
+    // verifier cannot recompute.
+    fn next_sent_output(&self) -> IKosResult<u32> {
+        let ctx = self.ctx.lock();
+        ctx[1].ikos_view.out_data.get(ctx[1].out_view_ctr).copied().ok_or_else(|| IKosError {
+            error: String::from("verify view length error"),
+        })
     }
 
     pub fn bit_and(mut self, rhs: &IKosVariable4V) -> IKosResult<Self> {
@@ -354,7 +545,7 @@ impl IKosVariable4V {
         }
 
         for (i, random) in rand.iter_mut().enumerate().take(2) {
-            *random = get_next_random_from_context(&mut self.ctx.borrow_mut()[i]).unwrap();
+            *random = get_next_random_from_context(&mut self.ctx.lock()[i]).unwrap();
         }
 
         let out = (self.value[0] & rhs.value[1])
@@ -363,25 +554,27 @@ impl IKosVariable4V {
             ^ rand[0]
             ^ rand[1];
 
-        if !IKosVariable4V::require_reconstruct(&self.ctx.borrow()) {
-            if out != self.ctx.borrow()[0].ikos_view.out_data[self.ctx.borrow()[0].out_view_ctr] {
-                return Err(IKosError {
-                    error: String::from("_IkosVariable4V & operation fail."),
-                });
-            }
-        } else {
-            self.ctx.borrow_mut()[0].ikos_view.out_data.push(out);
-        }
+        self.value[1] = self.next_sent_output()?;
         self.value[0] = out;
-        self.value[1] = self.ctx.borrow()[1].ikos_view.out_data[self.ctx.borrow()[1].out_view_ctr];
+        self.ctx.lock()[0].ikos_view.out_data.push(out);
         for i in 0..2 {
-            self.ctx.borrow_mut()[i].out_view_ctr += 1;
+            self.ctx.lock()[i].out_view_ctr += 1;
         }
 
         Ok(self)
     }
 
+    pub fn and_const(mut self, mask: u32) -> Self {
+        for i in 0..2 {
+            self.value[i] &= mask;
+        }
+        self
+    }
+
     pub fn bit_or(mut self, rhs: &IKosVariable4V) -> Self {
+        if self.is_empty_context() {
+            self.ctx = rhs.ctx.clone();
+        }
         for i in 0..2 {
             self.value[i] |= rhs.value[i];
         }
@@ -395,7 +588,7 @@ impl IKosVariable4V {
         let mut out = vec![0; 2];
         if self.is_empty_context() && rhs.is_empty_context() {
             for i in 0..2 {
-                self.value[i] += rhs.value[i];
+                self.value[i] = self.value[i].wrapping_add(rhs.value[i]);
             }
             return Ok(self);
         }
@@ -404,15 +597,11 @@ impl IKosVariable4V {
             return rhs.clone().add_op(&self);
         }
         for (i, random) in rand.iter_mut().enumerate().take(2) {
-            *random = get_next_random_from_context(&mut self.ctx.borrow_mut()[i]).unwrap();
+            *random = get_next_random_from_context(&mut self.ctx.lock()[i]).unwrap();
         }
-        let required = IKosVariable4V::require_reconstruct(&self.ctx.borrow());
-        for (i, out_value) in out.iter_mut().enumerate().take(2) {
-            if !required || i != 0 {
-                *out_value =
-                    self.ctx.borrow()[i].ikos_view.out_data[self.ctx.borrow()[i].out_view_ctr];
-            }
-            self.ctx.borrow_mut()[i].out_view_ctr += 1;
+        out[1] = self.next_sent_output()?;
+        for i in 0..2 {
+            self.ctx.lock()[i].out_view_ctr += 1;
         }
         for i in 0..31 {
             for j in 0..2 {
@@ -420,28 +609,152 @@ impl IKosVariable4V {
                 b[j] = get_bit!(rhs.value[j] ^ out[j], i);
             }
             let c = (a[0] & b[1]) ^ (a[1] & b[0]) ^ (get_bit!(rand[1], i));
-            if !required {
-                if c ^ (a[0] & b[0]) ^ (get_bit!(out[0], i)) ^ (get_bit!(rand[0], i))
-                    != get_bit!(out[0], i + 1)
-                {
-                    return Err(IKosError {
-                        error: String::from("IkosVariable4V + operation fail."),
-                    });
-                }
-            } else {
-                out[0] = set_bit!(
-                    out[0],
-                    i + 1,
-                    c ^ (a[0] & b[0]) ^ (get_bit!(out[0], i)) ^ (get_bit!(rand[0], i))
-                );
-            }
-        }
-        if required {
-            self.ctx.borrow_mut()[0].ikos_view.out_data.push(out[0]);
+            out[0] = set_bit!(
+                out[0],
+                i + 1,
+                c ^ (a[0] & b[0]) ^ (get_bit!(out[0], i)) ^ (get_bit!(rand[0], i))
+            );
         }
+        self.ctx.lock()[0].ikos_view.out_data.push(out[0]);
         for (i, &out_value) in out.iter().enumerate().take(2) {
             self.value[i] = self.value[i] ^ rhs.value[i] ^ out_value;
         }
         Ok(self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex::FromHex;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_random_tape_vectors() {
+        let vectors = include_str!("random_tape_vectors.txt");
+        for line in vectors.lines().filter(|line| !line.starts_with('#')) {
+            let mut fields = line.split_whitespace();
+            let seed = Vec::from_hex(fields.next().unwrap()).unwrap();
+            let mut ctx = IKosContext::new_with_seed(seed, vec![]);
+            for word in fields {
+                let expected = u32::from_str_radix(word, 16).unwrap();
+                assert_eq!(get_next_random_from_context(&mut ctx), Ok(expected));
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_tape_depends_on_seed_past_first_block() {
+        let mut ctx1 = IKosContext::new_with_seed(vec![1; RAND_TAPE_SEED_LEN], vec![]);
+        let mut ctx2 = IKosContext::new_with_seed(vec![2; RAND_TAPE_SEED_LEN], vec![]);
+        for _ in 0..8 {
+            get_next_random_from_context(&mut ctx1).unwrap();
+        }
+        // Force both tapes onto the same first block; the second must differ.
+        ctx2.randomness = ctx1.randomness.clone();
+        for _ in 0..8 {
+            get_next_random_from_context(&mut ctx2).unwrap();
+        }
+        assert_ne!(
+            get_next_random_from_context(&mut ctx1),
+            get_next_random_from_context(&mut ctx2)
+        );
+    }
+
+    // Splits `value` into three random shares on the given contexts.
+    fn share(value: u32, ctx: &SharedContexts, rng: &mut StdRng) -> IKosVariable4P {
+        let (r0, r1): (u32, u32) = (rng.gen(), rng.gen());
+        IKosVariable4P::new_share(vec![r0, r1, value ^ r0 ^ r1], ctx.clone())
+    }
+
+    #[test]
+    fn test_add_wraps_like_u32() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let edges = [0, 1, 2, 0x7fff_ffff, 0x8000_0000, 0xffff_fffe, u32::MAX];
+        let mut pairs: Vec<(u32, u32)> = edges.iter().flat_map(|&a| edges.iter().map(move |&b| (a, b))).collect();
+        pairs.extend((0..500).map(|_| (rng.gen(), rng.gen())));
+        for (a, b) in pairs {
+            let expected = a.wrapping_add(b);
+            let constant = IKosVariable4P::new_value(a).add_op(&IKosVariable4P::new_value(b));
+            assert_eq!(constant.value[0], expected);
+            let constant = IKosVariable4V::new_value(a).add_op(&IKosVariable4V::new_value(b)).unwrap();
+            assert_eq!(constant.value[0], expected);
+
+            let seeds: Vec<Vec<u8>> = (0..3).map(|_| (0..RAND_TAPE_SEED_LEN).map(|_| rng.gen()).collect()).collect();
+            let ctx = SharedContexts::new(seeds.iter().map(|s| IKosContext::new_with_seed(s.clone(), vec![])).collect());
+            let (x, y) = (share(a, &ctx, &mut rng), share(b, &ctx, &mut rng));
+            let sum = x.clone().add_op(&y);
+            assert_eq!(sum.value[0] ^ sum.value[1] ^ sum.value[2], expected, "{:#x} + {:#x}", a, b);
+
+            // The verifier recomputes the first two shares from the tapes
+            // and the second party's view.
+            let mut views: Vec<_> = seeds[..2].iter().map(|s| IKosContext::new_with_seed(s.clone(), vec![])).collect();
+            views[1].ikos_view.out_data = ctx.lock()[1].ikos_view.out_data.clone();
+            let verifier = SharedContexts::new(views);
+            let x4v = IKosVariable4V::new_share(x.value[..2].to_vec(), verifier.clone());
+            let y4v = IKosVariable4V::new_share(y.value[..2].to_vec(), verifier);
+            assert_eq!(x4v.add_op(&y4v).unwrap().value, sum.value[..2].to_vec());
+
+            let mixed = x.add_op(&IKosVariable4P::new_value(b));
+            assert_eq!(mixed.value[0] ^ mixed.value[1] ^ mixed.value[2], expected);
+        }
+    }
+
+    #[test]
+    fn test_constant_operand_takes_the_shared_context() {
+        // `c ^ x` with a constant left operand must stay a share; if it
+        // kept the constant's empty context, the AND of two such values
+        // would be taken share by share, which is not an AND of the values.
+        let mut rng = StdRng::seed_from_u64(3);
+        for i in 0..100 {
+            let (a, b, c, d): (u32, u32, u32, u32) = (rng.gen(), rng.gen(), rng.gen(), rng.gen());
+            let or = i % 2 == 1;
+            let expected = if or { (c | a) & (d | b) } else { (c ^ a) & (d ^ b) };
+            let seeds: Vec<Vec<u8>> = (0..3).map(|_| (0..RAND_TAPE_SEED_LEN).map(|_| rng.gen()).collect()).collect();
+            let ctx = SharedContexts::new(seeds.iter().map(|s| IKosContext::new_with_seed(s.clone(), vec![])).collect());
+            let (x, y) = (share(a, &ctx, &mut rng), share(b, &ctx, &mut rng));
+            let (c4p, d4p) = (IKosVariable4P::new_value(c), IKosVariable4P::new_value(d));
+            let (left, right) = if or { (c4p.bit_or(&x), d4p.bit_or(&y)) } else { (c4p.xor(&x), d4p.xor(&y)) };
+            let and = left.bit_and(&right);
+            assert_eq!(and.value[0] ^ and.value[1] ^ and.value[2], expected);
+
+            let mut views: Vec<_> = seeds[..2].iter().map(|s| IKosContext::new_with_seed(s.clone(), vec![])).collect();
+            views[1].ikos_view.out_data = ctx.lock()[1].ikos_view.out_data.clone();
+            let verifier = SharedContexts::new(views);
+            let x4v = IKosVariable4V::new_share(x.value[..2].to_vec(), verifier.clone());
+            let y4v = IKosVariable4V::new_share(y.value[..2].to_vec(), verifier);
+            let (c4v, d4v) = (IKosVariable4V::new_value(c), IKosVariable4V::new_value(d));
+            let (left, right) = if or { (c4v.bit_or(&x4v), d4v.bit_or(&y4v)) } else { (c4v.xor(&x4v), d4v.xor(&y4v)) };
+            assert_eq!(left.bit_and(&right).unwrap().value, and.value[..2].to_vec());
+        }
+    }
+
+    type ViewChange = Box<dyn Fn(&mut IKosView)>;
+
+    #[test]
+    fn test_commitment_binds_whole_view() {
+        let mut base = IKosContext::new_with_seed(vec![1; RAND_TAPE_SEED_LEN], vec![7; BLINDING_LEN]);
+        base.ikos_view.in_data = vec![1, 2];
+        base.ikos_view.out_data = vec![3];
+        let commitment = base.commit_ikos_context();
+
+        let mut changes: Vec<ViewChange> = vec![
+            Box::new(|view| view.rand_tape_seed[0] ^= 1),
+            Box::new(|view| view.blinding[0] ^= 1),
+            Box::new(|view| view.in_data[1] ^= 1),
+            Box::new(|view| view.out_data[0] ^= 1),
+            // Moving a word between in_data and out_data keeps the
+            // concatenation but not the length prefixes.
+            Box::new(|view| {
+                let word = view.in_data.pop().unwrap();
+                view.out_data.insert(0, word);
+            }),
+        ];
+        for change in changes.drain(..) {
+            let mut ctx = base.clone();
+            change(&mut ctx.ikos_view);
+            assert_ne!(ctx.commit_ikos_context(), commitment);
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+use crate::zkboo::circuit::{Circuit, CircuitStats, Gate, Wire};
+
+/// Statistics of a circuit before and after `optimize`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct OptimizeReport {
+    pub before: CircuitStats,
+    pub after: CircuitStats,
+}
+
+/// Rewrites `circuit` into an equivalent one with fewer gates, AND gates
+/// first, as those are what a proof carries:
+///
+/// - constant propagation, including `x & c` for a constant `c`, which
+///   becomes a local `AndConst` instead of an AND gate,
+/// - algebraic identities such as `x ^ x = 0`, `x & x = x`, `x & !x = 0`,
+///   `!!x = x` and merged rotations,
+/// - common subexpression elimination, with the operands of commutative
+///   gates ordered,
+/// - removal of gates no output depends on.
+///
+/// The result only depends on `circuit`, so the prover and the verifier
+/// optimizing the same circuit run the same gates.
+pub fn optimize(circuit: &Circuit) -> (Circuit, OptimizeReport) {
+    let mut optimizer = Optimizer {
+        out: Circuit::with_inputs(circuit.input_len(), circuit.input_pub_len()),
+        seen: HashMap::new(),
+    };
+    let mut map = Vec::with_capacity(circuit.gates().len());
+    for gate in circuit.gates() {
+        let gate = remap(*gate, &map);
+        map.push(optimizer.simplify(gate));
+    }
+    let outputs = circuit.outputs().iter().map(|&w| map[w]).collect();
+    let optimized = eliminate_dead_gates(&optimizer.out, outputs, circuit.error().is_some());
+    let report = OptimizeReport {
+        before: circuit.stats(),
+        after: optimized.stats(),
+    };
+    (optimized, report)
+}
+
+struct Optimizer {
+    out: Circuit,
+    seen: HashMap<Gate, Wire>,
+}
+
+enum Simplified {
+    Wire(Wire),
+    Gate(Gate),
+}
+
+fn remap(gate: Gate, map: &[Wire]) -> Gate {
+    match gate {
+        Gate::Input(_) | Gate::PublicInput(_) | Gate::Const(_) => gate,
+        Gate::Xor(a, b) => Gate::Xor(map[a], map[b]),
+        Gate::And(a, b) => Gate::And(map[a], map[b]),
+        Gate::AndConst(a, mask) => Gate::AndConst(map[a], mask),
+        Gate::Not(a) => Gate::Not(map[a]),
+        Gate::RotateRight(a, n) => Gate::RotateRight(map[a], n),
+        Gate::ShiftRight(a, n) => Gate::ShiftRight(map[a], n),
+        Gate::ShiftLeft(a, n) => Gate::ShiftLeft(map[a], n),
+        Gate::Add(a, b) => Gate::Add(map[a], map[b]),
+    }
+}
+
+impl Optimizer {
+    fn gate(&self, wire: Wire) -> Gate {
+        self.out.gates()[wire]
+    }
+
+    fn constant(&self, wire: Wire) -> Option<u32> {
+        match self.gate(wire) {
+            Gate::Const(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn is_not_of(&self, a: Wire, b: Wire) -> bool {
+        self.gate(a) == Gate::Not(b) || self.gate(b) == Gate::Not(a)
+    }
+
+    // Applies rewrites until the gate is an existing wire or cannot be
+    // simplified further, then emits it once.
+    fn simplify(&mut self, gate: Gate) -> Wire {
+        let mut gate = gate;
+        loop {
+            match self.rewrite(gate) {
+                Simplified::Wire(wire) => return wire,
+                Simplified::Gate(next) if next == gate => break,
+                Simplified::Gate(next) => gate = next,
+            }
+        }
+        let gate = match gate {
+            Gate::Xor(a, b) => Gate::Xor(a.min(b), a.max(b)),
+            Gate::And(a, b) => Gate::And(a.min(b), a.max(b)),
+            Gate::Add(a, b) => Gate::Add(a.min(b), a.max(b)),
+            gate => gate,
+        };
+        if let Some(&wire) = self.seen.get(&gate) {
+            return wire;
+        }
+        let wire = self.out.push(gate);
+        self.seen.insert(gate, wire);
+        wire
+    }
+
+    fn rewrite(&self, gate: Gate) -> Simplified {
+        use Simplified::{Gate as G, Wire as W};
+        match gate {
+            Gate::Xor(a, b) => match (self.constant(a), self.constant(b)) {
+                (Some(x), Some(y)) => G(Gate::Const(x ^ y)),
+                _ if a == b => G(Gate::Const(0)),
+                (Some(0), _) => W(b),
+                (_, Some(0)) => W(a),
+                (Some(u32::MAX), _) => G(Gate::Not(b)),
+                (_, Some(u32::MAX)) => G(Gate::Not(a)),
+                _ if self.is_not_of(a, b) => G(Gate::Const(u32::MAX)),
+                _ => G(gate),
+            },
+            Gate::And(a, b) => match (self.constant(a), self.constant(b)) {
+                (Some(x), Some(y)) => G(Gate::Const(x & y)),
+                (Some(mask), _) => G(Gate::AndConst(b, mask)),
+                (_, Some(mask)) => G(Gate::AndConst(a, mask)),
+                _ if a == b => W(a),
+                _ if self.is_not_of(a, b) => G(Gate::Const(0)),
+                _ => G(gate),
+            },
+            Gate::AndConst(a, mask) => match (self.constant(a), self.gate(a)) {
+                (Some(x), _) => G(Gate::Const(x & mask)),
+                _ if mask == 0 => G(Gate::Const(0)),
+                _ if mask == u32::MAX => W(a),
+                (_, Gate::AndConst(x, inner)) => G(Gate::AndConst(x, mask & inner)),
+                _ => G(gate),
+            },
+            Gate::Not(a) => match (self.constant(a), self.gate(a)) {
+                (Some(x), _) => G(Gate::Const(!x)),
+                (_, Gate::Not(x)) => W(x),
+                _ => G(gate),
+            },
+            Gate::RotateRight(a, n) => match (self.constant(a), self.gate(a)) {
+                _ if n % 32 == 0 => W(a),
+                (Some(x), _) => G(Gate::Const(x.rotate_right(n))),
+                (_, Gate::RotateRight(x, m)) => G(Gate::RotateRight(x, (n + m) % 32)),
+                _ => G(Gate::RotateRight(a, n % 32)),
+            },
+            Gate::ShiftRight(a, n) => match self.constant(a) {
+                _ if n == 0 => W(a),
+                _ if n >= 32 => G(Gate::Const(0)),
+                Some(x) => G(Gate::Const(x >> n)),
+                None => G(gate),
+            },
+            Gate::ShiftLeft(a, n) => match self.constant(a) {
+                _ if n == 0 => W(a),
+                _ if n >= 32 => G(Gate::Const(0)),
+                Some(x) => G(Gate::Const(x << n)),
+                None => G(gate),
+            },
+            Gate::Add(a, b) => match (self.constant(a), self.constant(b)) {
+                (Some(x), Some(y)) => G(Gate::Const(x.wrapping_add(y))),
+                (Some(0), _) => W(b),
+                (_, Some(0)) => W(a),
+                _ => G(gate),
+            },
+            Gate::Input(_) | Gate::PublicInput(_) | Gate::Const(_) => G(gate),
+        }
+    }
+}
+
+// Keeps only the gates the outputs depend on, in their original order.
+// With `has_error` the last output stays the error word.
+fn eliminate_dead_gates(circuit: &Circuit, outputs: Vec<Wire>, has_error: bool) -> Circuit {
+    let gates = circuit.gates();
+    let mut live = vec![false; gates.len()];
+    for &wire in &outputs {
+        live[wire] = true;
+    }
+    for wire in (0..gates.len()).rev() {
+        if !live[wire] {
+            continue;
+        }
+        match gates[wire] {
+            Gate::Xor(a, b) | Gate::And(a, b) | Gate::Add(a, b) => {
+                live[a] = true;
+                live[b] = true;
+            }
+            Gate::AndConst(a, _)
+            | Gate::Not(a)
+            | Gate::RotateRight(a, _)
+            | Gate::ShiftRight(a, _)
+            | Gate::ShiftLeft(a, _) => live[a] = true,
+            Gate::Input(_) | Gate::PublicInput(_) | Gate::Const(_) => {}
+        }
+    }
+    let mut res = Circuit::with_inputs(circuit.input_len(), circuit.input_pub_len());
+    let mut map = vec![0; gates.len()];
+    for (wire, gate) in gates.iter().enumerate() {
+        if live[wire] {
+            map[wire] = res.push(remap(*gate, &map));
+        }
+    }
+    let mut outputs: Vec<_> = outputs.iter().map(|&w| map[w]).collect();
+    let error = if has_error { outputs.pop() } else { None };
+    res.set_outputs(outputs);
+    if let Some(error) = error {
+        res.set_error(error);
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zkboo::ikos::IKosVariable4P;
+
+    fn eval(circuit: &Circuit, input: &[u32]) -> Vec<u32> {
+        let input: Vec<_> = input.iter().map(|&v| IKosVariable4P::new_value(v)).collect();
+        circuit.eval(&input, &[]).unwrap().iter().map(|v| v.value[0]).collect()
+    }
+
+    #[test]
+    fn test_constants_fold_and_masks_become_local() {
+        let mut circuit = Circuit::new();
+        let a = circuit.input();
+        let one = circuit.constant(1);
+        let two = circuit.constant(2);
+        let three = circuit.add(one, two);
+        let mask = circuit.constant(0xff);
+        let masked = circuit.and(mask, a);
+        let picked = circuit.mux(mask, a, three);
+        let sum = circuit.add(masked, picked);
+        circuit.set_outputs(vec![sum]);
+
+        let (optimized, report) = optimize(&circuit);
+        assert_eq!(report.before.and_gates, 2);
+        assert_eq!(report.before.add_gates, 2);
+        assert_eq!(report.after.and_gates, 0);
+        assert_eq!(report.after.and_const_gates, 2);
+        assert_eq!(report.after.add_gates, 1);
+        for &x in [0, 0x1234, 0xff00_ff00].iter() {
+            assert_eq!(eval(&optimized, &[x]), eval(&circuit, &[x]));
+        }
+    }
+
+    #[test]
+    fn test_identities_and_common_subexpressions() {
+        let mut circuit = Circuit::new();
+        let a = circuit.input();
+        let b = circuit.input();
+        let ab = circuit.and(a, b);
+        let ba = circuit.and(b, a);
+        let not_a = circuit.not(a);
+        let not_not_a = circuit.not(not_a);
+        let contradiction = circuit.and(a, not_a);
+        let zero = circuit.xor(ab, ba);
+        let same = circuit.and(not_not_a, a);
+        let r1 = circuit.rotate_right(same, 20);
+        let r2 = circuit.rotate_right(r1, 12);
+        let dead = circuit.add(a, b);
+        let _ = circuit.equal(dead, a);
+        let x = circuit.xor(zero, contradiction);
+        let out = circuit.xor(x, r2);
+        circuit.set_outputs(vec![out, ab]);
+
+        let (optimized, report) = optimize(&circuit);
+        // Only `a & b` is left, shared by both outputs.
+        assert_eq!(report.before.view_words(), 10);
+        assert_eq!(report.after.view_words(), 1);
+        assert_eq!(optimized.gates(), &[Gate::Input(0), Gate::Input(1), Gate::And(0, 1)]);
+        assert_eq!(optimized.outputs(), &[0, 2]);
+        assert_eq!(optimized.input_len(), 2);
+    }
+
+    #[test]
+    fn test_error_word_is_kept() {
+        let mut circuit = Circuit::new();
+        let a = circuit.input();
+        let three = circuit.constant(3);
+        let (sum, overflow) = circuit.add_checked(a, three);
+        circuit.set_outputs(vec![sum]);
+        circuit.set_error(overflow);
+
+        let (optimized, _) = optimize(&circuit);
+        assert_eq!(optimized.error(), Some(*optimized.outputs().last().unwrap()));
+        assert_eq!(eval(&optimized, &[u32::MAX - 2]), vec![0, u32::MAX]);
+        assert_eq!(eval(&optimized, &[4]), vec![7, 0]);
+    }
+
+    #[test]
+    fn test_dead_inputs_keep_the_input_layout() {
+        let mut circuit = Circuit::new();
+        let _ = circuit.input();
+        let b = circuit.input();
+        let p = circuit.public_input();
+        let out = circuit.xor(b, p);
+        circuit.set_outputs(vec![out]);
+
+        let (optimized, _) = optimize(&circuit);
+        assert_eq!(optimized.gates(), &[Gate::Input(1), Gate::PublicInput(0), Gate::Xor(0, 1)]);
+        let input: Vec<_> = [5, 6].iter().map(|&v| IKosVariable4P::new_value(v)).collect();
+        assert_eq!(optimized.eval(&input, &[3]).unwrap()[0].value[0], 5);
+    }
+}
@@ -0,0 +1,101 @@
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+use hex::FromHex;
+use crate::zkboo::utils::{convert_u32_to_u8, convert_usize_to_u8};
+
+const CIRCUIT_LABEL: &[u8] = b"zoker.circuit";
+const CHALLENGE_LABEL: &[u8] = b"zoker.fiat-shamir";
+
+/// The statement a proof is about: the circuit that was run and an
+/// optional context chosen by the caller (e.g. a chain id or a nonce).
+///
+/// Both are absorbed into the Fiat-Shamir challenge together with the
+/// public inputs and the claimed output, so a proof only verifies against
+/// the statement it was made for.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Transcript {
+    circuit_digest: [u8; 32],
+    context: Vec<u8>,
+}
+
+impl Transcript {
+    pub fn new(circuit_digest: [u8; 32]) -> Self {
+        Transcript {
+            circuit_digest,
+            context: vec![],
+        }
+    }
+
+    pub fn with_context(mut self, context: Vec<u8>) -> Self {
+        self.context = context;
+        self
+    }
+
+    pub fn context(&self) -> &[u8] {
+        &self.context
+    }
+
+    /// sha256("zoker.circuit" || source), where `source` is the canonical
+    /// source of the circuit.
+    pub fn circuit_digest(source: &str) -> [u8; 32] {
+        let mut vec = CIRCUIT_LABEL.to_vec();
+        vec.extend(source.as_bytes());
+        sha256(&vec)
+    }
+
+    /// sha256("zoker.fiat-shamir" || circuit_digest || len(context) || context
+    /// || input_len || len(input_pub) || input_pub || len(output) || output
+    /// || out_data || three_views), lengths and words as big-endian u32.
+    pub fn challenge(
+        &self,
+        input_len: usize,
+        input_pub: &[u32],
+        output: &[u32],
+        out_data: &[u32],
+        three_views: &[u8],
+    ) -> [u8; 32] {
+        let mut vec = CHALLENGE_LABEL.to_vec();
+        vec.extend(&self.circuit_digest);
+        vec.extend(convert_usize_to_u8(self.context.len()));
+        vec.extend(&self.context);
+        vec.extend(convert_usize_to_u8(input_len));
+        vec.extend(convert_usize_to_u8(input_pub.len()));
+        vec.extend(convert_u32_to_u8(input_pub));
+        vec.extend(convert_usize_to_u8(output.len()));
+        vec.extend(convert_u32_to_u8(output));
+        vec.extend(convert_u32_to_u8(out_data));
+        vec.extend(three_views);
+        sha256(&vec)
+    }
+}
+
+fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut sha = Sha256::new();
+    sha.input(data);
+    <[u8; 32]>::from_hex(sha.result_str()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn challenge(transcript: &Transcript, input_pub: &[u32], output: &[u32]) -> [u8; 32] {
+        transcript.challenge(1, input_pub, output, &[5, 6], &[7; 4])
+    }
+
+    #[test]
+    fn test_challenge_binds_statement() {
+        let transcript = Transcript::new(Transcript::circuit_digest("contract A {\n}\n"));
+        let base = challenge(&transcript, &[1, 2], &[3]);
+        assert_eq!(base, challenge(&transcript.clone(), &[1, 2], &[3]));
+
+        let other_circuit = Transcript::new(Transcript::circuit_digest("contract B {\n}\n"));
+        assert_ne!(base, challenge(&other_circuit, &[1, 2], &[3]));
+        let other_context = transcript.clone().with_context(b"chain 1".to_vec());
+        assert_ne!(base, challenge(&other_context, &[1, 2], &[3]));
+        assert_ne!(base, challenge(&transcript, &[1, 3], &[3]));
+        assert_ne!(base, challenge(&transcript, &[1, 2], &[4]));
+        // Same words, split differently between input_pub and output.
+        assert_ne!(base, challenge(&transcript, &[1], &[2, 3]));
+    }
+}
@@ -18,3 +18,13 @@ pub fn convert_u32_to_u8(vec: &[u32]) -> Vec<u8> {
     }
     res
 }
+
+/// Packs bytes big-endian into `word_len` words, zero-padding on the right
+/// like a Solidity `bytesN` value. Bytes beyond `word_len * 4` are dropped.
+pub fn convert_u8_to_u32(bytes: &[u8], word_len: usize) -> Vec<u32> {
+    let mut res = vec![0; word_len];
+    for (i, &byte) in bytes.iter().take(word_len * 4).enumerate() {
+        res[i / 4] |= (byte as u32) << (24 - 8 * (i % 4));
+    }
+    res
+}
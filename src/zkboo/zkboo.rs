@@ -1,16 +1,16 @@
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use crypto::digest::Digest;
 use crypto::sha2::Sha256;
-use hex::FromHex;
-use std::borrow::Borrow;
-use std::cell::RefCell;
-use std::rc::Rc;
-use crate::zkboo::ikos::{get_next_random_from_context, IKosContext, IKosError, IKosResult, IKosVariable4P, IKosVariable4V, IKosView};
-use crate::zkboo::utils::{convert_u32_to_u8, convert_usize_to_u8};
+use crate::zkboo::bitsliced::{batch_contexts, from_planes, to_planes, Bitsliced4P, BITSLICE_ROUNDS};
+use crate::zkboo::circuit::Circuit;
+use crate::zkboo::ikos::{get_next_random_from_context, party_seeds, IKosContext, IKosError, IKosResult, IKosVariable4P, IKosVariable4V, IKosView, OsSeeder, SharedContexts, TapeSeeder, BLINDING_LEN, RAND_TAPE_SEED_LEN, ROUND_SEED_LEN};
+use crate::zkboo::transcript::Transcript;
 use crate::zkboo::vector::_3DVector;
 
-fn find_shares(input: u32, ctx: Rc<RefCell<Vec<IKosContext>>>) -> IKosResult<IKosVariable4P> {
+fn find_shares(input: u32, ctx: SharedContexts) -> IKosResult<IKosVariable4P> {
     let shares = {
-        let mut contexts = (*ctx).borrow_mut();
+        let mut contexts = ctx.lock();
         let share0 = get_next_random_from_context(&mut contexts[0])?;
         let share1 = get_next_random_from_context(&mut contexts[1])?;
         vec![share0, share1, input ^ share0 ^ share1]
@@ -19,12 +19,66 @@ fn find_shares(input: u32, ctx: Rc<RefCell<Vec<IKosContext>>>) -> IKosResult<IKo
     Ok(IKosVariable4P::new_share(shares, ctx))
 }
 
-pub trait Circuit4PTrait {
-    fn circuit(&mut self, input: Vec<IKosVariable4P>, input_pub: &[u32]) -> Vec<IKosVariable4P>;
+/// Runs `f` on every round, on the rayon thread pool when the `parallel`
+/// feature is enabled. Results keep the round order.
+fn map_rounds<T, R, F>(rounds: Vec<T>, f: F) -> IKosResult<Vec<R>>
+where
+    T: Send,
+    R: Send,
+    F: Fn(usize, T) -> IKosResult<R> + Sync,
+{
+    #[cfg(feature = "parallel")]
+    let rounds = rounds.into_par_iter();
+    #[cfg(not(feature = "parallel"))]
+    let rounds = rounds.into_iter();
+    rounds.enumerate().map(|(round, item)| f(round, item)).collect()
 }
 
-pub type Circuit4P = Box<dyn Fn(Vec<IKosVariable4P>, &[u32]) -> Vec<IKosVariable4P>>;
-type Circuit4V = fn(&[IKosVariable4V], &[u32]) -> IKosResult<Vec<IKosVariable4V>>;
+/// A circuit run by the prover. Rounds may run concurrently, so `circuit`
+/// takes `&self` and keeps any scratch state local to the call.
+pub trait Circuit4PTrait: Send + Sync {
+    fn circuit(&self, input: Vec<IKosVariable4P>, input_pub: &[u32]) -> Vec<IKosVariable4P>;
+
+    /// The same circuit over a batch of rounds, or `None` if it only runs
+    /// on the per-round engine.
+    fn circuit_bitsliced(&self, _input: Vec<Bitsliced4P>, _input_pub: &[u32]) -> Option<Vec<Bitsliced4P>> {
+        None
+    }
+
+    /// Whether `output` passes the circuit's own checks; the prover
+    /// refuses to prove an output that does not.
+    fn accepts(&self, _output: &[u32]) -> bool {
+        true
+    }
+}
+
+/// A circuit run by the verifier on the two opened views of a round.
+pub trait Circuit4VTrait: Send + Sync {
+    fn circuit(&self, input: &[IKosVariable4V], input_pub: &[u32]) -> IKosResult<Vec<IKosVariable4V>>;
+
+    /// Whether `output` passes the circuit's own checks; a proof of an
+    /// output that does not is rejected.
+    fn accepts(&self, _output: &[u32]) -> bool {
+        true
+    }
+}
+
+impl Circuit4VTrait for Circuit4V {
+    fn circuit(&self, input: &[IKosVariable4V], input_pub: &[u32]) -> IKosResult<Vec<IKosVariable4V>> {
+        self(input, input_pub)
+    }
+}
+
+/// How the prover evaluates the circuit. Both engines give the same proof.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Engine {
+    /// One round at a time.
+    PerRound,
+    /// `BITSLICE_ROUNDS` rounds at a time, see `bitsliced`.
+    Bitsliced,
+}
+
+pub type Circuit4V = fn(&[IKosVariable4V], &[u32]) -> IKosResult<Vec<IKosVariable4V>>;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct ZkBoo {
@@ -43,6 +97,7 @@ pub struct Proof {
     pub out_data: Vec<u32>,
     pub three_views: Vec<u8>,
     pub views: Vec<IKosView>,
+    pub transcript: Transcript,
 }
 
 pub struct ProvingProof {
@@ -50,6 +105,16 @@ pub struct ProvingProof {
     input_pub: Vec<u32>,
     output_len: usize,
     circuit: Box<dyn Circuit4PTrait>,
+    seeder: Box<dyn TapeSeeder>,
+    transcript: Transcript,
+    engine: Engine,
+}
+
+// Commitments of all parties of one round and their output shares,
+// party-minor.
+struct RoundCommitments {
+    three_views: Vec<u8>,
+    out_shares: Vec<u32>,
 }
 
 pub struct VerifyingProof {
@@ -59,7 +124,8 @@ pub struct VerifyingProof {
     challenge: [u8; 32],
     two_views: Vec<u8>,
     response: Vec<IKosView>,
-    circuit: Circuit4V,
+    circuit: Box<dyn Circuit4VTrait>,
+    transcript: Transcript,
 }
 
 impl ZkBoo {
@@ -77,46 +143,38 @@ impl ZkBoo {
         }
     }
 
-    pub fn prove(&self, mut proof: ProvingProof) -> IKosResult<Proof> {
-        let mut vec_view = _3DVector::new(proof.output_len, self.num_of_round, self.num_of_branch);
-        let mut three_views = vec![0; self.num_of_round * self.num_of_branch * self.commit_length];
-        let mut views = vec![];
-        for round in 0..self.num_of_round {
-            let mut ctx = vec![];
-            let mut ikos_input = vec![];
+    pub fn num_of_round(&self) -> usize {
+        self.num_of_round
+    }
 
-            for _ in 0..self.num_of_branch {
-                ctx.push(IKosContext::new());
-            }
-            let ctx = Rc::new(RefCell::new(ctx));
-            for i in 0..proof.input.len() {
-                ikos_input.push(find_shares(proof.input[i], Rc::clone(&ctx))?);
-                let mut contexts = (*ctx).borrow_mut();
-                contexts[2].ikos_view.in_data.push(ikos_input[i].value[2]);
+    pub fn prove(&self, mut proof: ProvingProof) -> IKosResult<Proof> {
+        // Seeds are drawn in round order so a seeder replays the same proof
+        // however the rounds are scheduled.
+        let round_seeds: Vec<_> = (0..self.num_of_round)
+            .map(|round| proof.seeder.round_seed(round))
+            .collect();
+        let circuit = &*proof.circuit;
+        let (input, input_pub) = (&proof.input, &proof.input_pub);
+        let rounds = match proof.engine {
+            Engine::PerRound => map_rounds(round_seeds, |_, round_seed| {
+                self.prove_round(circuit, input, input_pub, &round_seed)
+            })?,
+            Engine::Bitsliced => {
+                let batches = round_seeds.chunks(BITSLICE_ROUNDS).map(<[_]>::to_vec).collect();
+                let batches = map_rounds(batches, |_, batch: Vec<[u8; ROUND_SEED_LEN]>| {
+                    self.prove_batch(circuit, input, input_pub, &batch)
+                })?;
+                batches.into_iter().flatten().collect()
             }
-            // Circuit 실행
-            let input = ikos_input.clone();
-
-            let ikos_output = proof.circuit.circuit(input, &proof.input_pub.clone());
+        };
 
-            // ikos output 저장
-            for (party, context) in (*ctx).borrow_mut().iter_mut().enumerate() {
-                for (i, ikos) in ikos_output.iter().enumerate() {
-                    let index = vec_view.get_index(i, round, party);
-                    vec_view.data[index] = ikos.value[party];
-                    context.ikos_view.out_data.push(ikos.value[party]);
-                }
-            }
-            // commitment
-            for party in 0..self.num_of_branch {
-                let commit = (*ctx).borrow_mut()[party].commit_ikos_context();
-                for (i, &cmt) in commit.iter().enumerate() {
-                    three_views[round * self.num_of_branch * self.commit_length
-                        + party * self.commit_length
-                        + i] = cmt;
-                }
-            }
-            views.extend((*ctx).borrow_mut().iter().map(|c| c.ikos_view.clone()));
+        let mut vec_view = _3DVector::new(proof.output_len, self.num_of_round, self.num_of_branch);
+        let mut three_views = vec![];
+        let mut views = vec![];
+        for (round, (round_views, commitments)) in rounds.into_iter().enumerate() {
+            self.store_out_shares(&mut vec_view, round, &commitments.out_shares);
+            three_views.extend(commitments.three_views);
+            views.extend(round_views);
         }
         // 계산 결과 구하기
         let mut out = vec![];
@@ -127,7 +185,12 @@ impl ZkBoo {
                     ^ vec_view.data[vec_view.get_index(i, 0, 2)],
             )
         }
-        Ok(Proof::new(
+        if !circuit.accepts(&out) {
+            return Err(IKosError {
+                error: String::from("output fails the circuit checks"),
+            });
+        }
+        let mut res = Proof::new(
             proof.input_pub.clone(),
             proof.input.len(),
             proof.input_pub.len(),
@@ -135,187 +198,148 @@ impl ZkBoo {
             out,
             three_views,
             views,
-        ))
+        );
+        res.transcript = proof.transcript;
+        Ok(res)
     }
 
-    pub fn verify(&self, proof: VerifyingProof) -> IKosResult<bool> {
-        let index_vec = self.choose_index_from_challenge(&proof.challenge);
-        let mut vec_view =
-            _3DVector::new(proof.output.len(), self.num_of_round, self.num_of_branch);
-        let mut three_views = vec![0; self.num_of_round * self.num_of_branch * self.commit_length];
-        let mut three_offset = 0;
-        let mut two_offset = 0;
-        for (round, &index) in index_vec.iter().enumerate().take(self.num_of_round) {
-            let mut ctx = vec![];
-            for party in 0..self.num_of_public_branch {
-                ctx.push(IKosContext::new_views(
-                    proof.response[round * self.num_of_public_branch + party].clone(),
-                ));
-            }
-            match index_vec[round] {
-                0 => {
-                    for _ in 0..proof.input_len {
-                        let data = get_next_random_from_context(&mut ctx[0])?;
-                        ctx[0].ikos_view.in_data.push(data);
-                    }
-                }
-                1 => {
-                    for _ in 0..proof.input_len {
-                        let data = get_next_random_from_context(&mut ctx[1])?;
-                        ctx[1].ikos_view.in_data.push(data);
-                    }
-                }
-                2 => {
-                    for _ in 0..proof.input_len {
-                        let data = get_next_random_from_context(&mut ctx[0])?;
-                        ctx[0].ikos_view.in_data.push(data);
-                        let data = get_next_random_from_context(&mut ctx[1])?;
-                        ctx[1].ikos_view.in_data.push(data);
-                    }
-                }
-                _ => {
-                    return Err(IKosError {
-                        error: String::from("index error"),
-                    });
-                }
+    fn prove_round(
+        &self,
+        circuit: &dyn Circuit4PTrait,
+        input: &[u32],
+        input_pub: &[u32],
+        round_seed: &[u8; ROUND_SEED_LEN],
+    ) -> IKosResult<(Vec<IKosView>, RoundCommitments)> {
+        let mut ctx = vec![];
+        for party in 0..self.num_of_branch {
+            let (tape_seed, blinding) = party_seeds(round_seed, party);
+            ctx.push(IKosContext::new_with_seed(tape_seed, blinding));
+        }
+        let ctx = SharedContexts::new(ctx);
+        let mut ikos_input = vec![];
+        for &value in input {
+            let share = find_shares(value, ctx.clone())?;
+            for (party, context) in ctx.lock().iter_mut().enumerate() {
+                context.ikos_view.in_data.push(share.value[party]);
             }
+            ikos_input.push(share);
+        }
+        // Circuit 실행
+        let ikos_output = circuit.circuit(ikos_input, input_pub);
 
-            let ctx = Rc::new(RefCell::new(ctx));
-            // input
-            let mut ikos_input = vec![];
-            for i in 0..proof.input_len {
-                let mut shares = vec![];
-                let contexts = (*ctx).borrow();
-                for context in contexts.iter().take(self.num_of_public_branch) {
-                    shares.push(context.ikos_view.in_data[i]);
-                }
-                ikos_input.push(IKosVariable4V::new_share(shares, Rc::clone(ctx.borrow())));
+        // ikos output 저장
+        let mut out_shares = vec![];
+        for ikos in &ikos_output {
+            out_shares.extend(&ikos.value);
+        }
+        let mut contexts = ctx.lock();
+        for (party, context) in contexts.iter_mut().enumerate() {
+            for ikos in &ikos_output {
+                context.ikos_view.out_data.push(ikos.value[party]);
             }
+        }
+        // commitment
+        let mut three_views = vec![];
+        for context in contexts.iter_mut() {
+            three_views.extend(&context.commit_ikos_context());
+        }
+        let views = contexts.iter().map(|c| c.ikos_view.clone()).collect();
+        Ok((views, RoundCommitments { three_views, out_shares }))
+    }
 
-            // rut circuit
-            let ikos_out = proof.run_circuit(&ikos_input, &proof.input_pub)?;
-
-            let required = IKosVariable4V::require_reconstruct(&**(*ctx).borrow());
-            let mut contexts = (*ctx).borrow_mut();
-            for (branch, context) in contexts
-                .iter_mut()
-                .enumerate()
-                .take(self.num_of_public_branch)
-            {
-                for ikos in &ikos_out {
-                    if !required || branch != 0 {
-                        if ikos.value[branch] != context.ikos_view.out_data[context.out_view_ctr] {
-                            return Err(IKosError {
-                                error: String::from("verify output value error"),
-                            });
-                        }
-                    } else {
-                        context.ikos_view.out_data.push(ikos.value[branch]);
-                        context.out_view_ctr += 1;
-                    }
+    // Proves several rounds with one run of the bitsliced circuit.
+    fn prove_batch(
+        &self,
+        circuit: &dyn Circuit4PTrait,
+        input: &[u32],
+        input_pub: &[u32],
+        round_seeds: &[[u8; ROUND_SEED_LEN]],
+    ) -> IKosResult<Vec<(Vec<IKosView>, RoundCommitments)>> {
+        let rounds = round_seeds.len();
+        let contexts = round_seeds
+            .iter()
+            .map(|round_seed| {
+                (0..self.num_of_branch)
+                    .map(|party| {
+                        let (tape_seed, blinding) = party_seeds(round_seed, party);
+                        IKosContext::new_with_seed(tape_seed, blinding)
+                    })
+                    .collect()
+            })
+            .collect();
+        let ctx = batch_contexts(contexts);
+        let mut ikos_input = vec![];
+        for &value in input {
+            let mut shares = vec![vec![]; self.num_of_branch];
+            let mut contexts = ctx.lock();
+            for round in contexts.chunks_mut(self.num_of_branch) {
+                let share0 = get_next_random_from_context(&mut round[0])?;
+                let share1 = get_next_random_from_context(&mut round[1])?;
+                for (party, &share) in [share0, share1, value ^ share0 ^ share1].iter().enumerate() {
+                    round[party].ikos_view.in_data.push(share);
+                    shares[party].push(share);
                 }
             }
-
-            // construct three views
-            match index {
-                0 => {
-                    three_views[three_offset..(three_offset + self.commit_length)]
-                        .clone_from_slice(
-                            &proof.two_views[two_offset..(two_offset + self.commit_length)],
-                        );
-                    three_offset += self.commit_length;
-                    two_offset += self.commit_length;
-                    let commit = contexts[0].commit_ikos_context();
-                    three_views[three_offset..(three_offset + self.commit_length)]
-                        .clone_from_slice(&commit);
-                    three_offset += self.commit_length;
-                    let commit = contexts[1].commit_ikos_context();
-                    three_views[three_offset..(three_offset + self.commit_length)]
-                        .clone_from_slice(&commit);
-                    three_offset += self.commit_length;
-                }
-                1 => {
-                    let commit = contexts[1].commit_ikos_context();
-                    three_views[three_offset..(three_offset + self.commit_length)]
-                        .clone_from_slice(&commit);
-                    three_offset += self.commit_length;
-                    three_views[three_offset..(three_offset + self.commit_length)]
-                        .clone_from_slice(
-                            &proof.two_views[two_offset..(two_offset + self.commit_length)],
-                        );
-                    three_offset += self.commit_length;
-                    two_offset += self.commit_length;
-                    let commit = contexts[0].commit_ikos_context();
-                    three_views[three_offset..(three_offset + self.commit_length)]
-                        .clone_from_slice(&commit);
-                    three_offset += self.commit_length;
-                }
-                2 => {
-                    let commit = contexts[0].commit_ikos_context();
-                    three_views[three_offset..(three_offset + self.commit_length)]
-                        .clone_from_slice(&commit);
-                    three_offset += self.commit_length;
-                    let commit = contexts[1].commit_ikos_context();
-                    three_views[three_offset..(three_offset + self.commit_length)]
-                        .clone_from_slice(&commit);
-                    three_offset += self.commit_length;
-
-                    three_views[three_offset..(three_offset + self.commit_length)]
-                        .clone_from_slice(
-                            &proof.two_views[two_offset..(two_offset + self.commit_length)],
-                        );
-                    three_offset += self.commit_length;
-                    two_offset += self.commit_length;
-                }
-                _ => {
-                    return Err(IKosError {
-                        error: String::from("verify index error"),
-                    });
-                }
+            let planes = shares.iter().map(|words| to_planes(words)).collect();
+            ikos_input.push(Bitsliced4P::new_share(planes, ctx.clone()));
+        }
+        let ikos_output = circuit.circuit_bitsliced(ikos_input, input_pub).ok_or_else(|| IKosError {
+            error: String::from("circuit has no bitsliced form"),
+        })?;
+
+        // Output words of every party, per round.
+        let outputs: Vec<Vec<Vec<u32>>> = ikos_output
+            .iter()
+            .map(|ikos| ikos.planes.iter().map(|planes| from_planes(planes, rounds)).collect())
+            .collect();
+        let mut contexts = ctx.lock();
+        let mut res = vec![];
+        for (round, round_contexts) in contexts.chunks_mut(self.num_of_branch).enumerate() {
+            let mut out_shares = vec![];
+            for output in &outputs {
+                out_shares.extend(output.iter().map(|words| words[round]));
             }
-
-            // rebuild shares
-            for (i, ikos) in ikos_out.iter().enumerate() {
-                let pos = vec_view.get_index(i, round, 0);
-                match index {
-                    0 => {
-                        vec_view.data[pos + 1] = ikos.value[0];
-                        vec_view.data[pos + 2] = ikos.value[1];
-                        vec_view.data[pos] =
-                            proof.output[i] ^ vec_view.data[pos + 2] ^ vec_view.data[pos + 1];
-                    }
-                    1 => {
-                        vec_view.data[pos] = ikos.value[1];
-                        vec_view.data[pos + 2] = ikos.value[0];
-                        vec_view.data[pos + 1] =
-                            proof.output[i] ^ vec_view.data[pos] ^ vec_view.data[pos + 2];
-                    }
-                    2 => {
-                        vec_view.data[pos] = ikos.value[0];
-                        vec_view.data[pos + 1] = ikos.value[1];
-                        vec_view.data[pos + 2] =
-                            proof.output[i] ^ vec_view.data[pos + 1] ^ vec_view.data[pos];
-                    }
-                    _ => {
-                        return Err(IKosError {
-                            error: String::from("verify index error"),
-                        });
-                    }
+            for (party, context) in round_contexts.iter_mut().enumerate() {
+                for output in &outputs {
+                    context.ikos_view.out_data.push(output[party][round]);
                 }
             }
+            let mut three_views = vec![];
+            for context in round_contexts.iter_mut() {
+                three_views.extend(&context.commit_ikos_context());
+            }
+            let views = round_contexts.iter().map(|c| c.ikos_view.clone()).collect();
+            res.push((views, RoundCommitments { three_views, out_shares }));
         }
-        let mut out = vec![];
-        for i in 0..proof.output.len() {
-            out.push(
-                vec_view.data[vec_view.get_index(i, 0, 0)]
-                    ^ vec_view.data[vec_view.get_index(i, 0, 1)]
-                    ^ vec_view.data[vec_view.get_index(i, 0, 2)],
-            )
+        Ok(res)
+    }
+
+    pub fn verify(&self, proof: VerifyingProof) -> IKosResult<bool> {
+        if !proof.circuit.accepts(&proof.output) {
+            return Ok(false);
+        }
+        let index_vec = self.choose_index_from_challenge(&proof.challenge);
+        if proof.response.len() != self.num_of_round * self.num_of_public_branch
+            || proof.two_views.len() != self.num_of_round * self.commit_length
+        {
+            return Err(IKosError {
+                error: String::from("verify proof length error"),
+            });
+        }
+        let rounds = map_rounds(index_vec, |round, index| self.verify_round(&proof, round, index))?;
+
+        let mut vec_view =
+            _3DVector::new(proof.output.len(), self.num_of_round, self.num_of_branch);
+        let mut three_views = vec![];
+        for (round, commitments) in rounds.into_iter().enumerate() {
+            self.store_out_shares(&mut vec_view, round, &commitments.out_shares);
+            three_views.extend(commitments.three_views);
         }
 
-        let commit = ZkBoo::query_random_oracle(
+        let commit = proof.transcript.challenge(
             proof.input_len,
-            proof.output.len(),
+            &proof.input_pub,
+            &proof.output,
             &vec_view.data,
             &three_views,
         );
@@ -328,32 +352,128 @@ impl ZkBoo {
         Ok(true)
     }
 
-    pub fn query_random_oracle(
-        input_len: usize,
-        output_len: usize,
-        out_data: &[u32],
-        three_views: &[u8],
-    ) -> [u8; 32] {
-        let mut sha = Sha256::new();
-        let mut vec = convert_usize_to_u8(input_len);
-        vec.extend(convert_usize_to_u8(output_len));
-        vec.extend(convert_u32_to_u8(&out_data));
-        vec.extend(three_views);
-        sha.input(vec.as_ref());
-        let res = sha.result_str();
-        <[u8; 32]>::from_hex(res).unwrap()
+    fn verify_round(&self, proof: &VerifyingProof, round: usize, index: usize) -> IKosResult<RoundCommitments> {
+        let mut ctx = vec![];
+        for party in 0..self.num_of_public_branch {
+            ctx.push(IKosContext::new_views(
+                proof.response[round * self.num_of_public_branch + party].clone(),
+            ));
+        }
+        // Input shares of parties 0 and 1 come from their tapes and
+        // replace whatever the proof sent; the commitment binds them.
+        let derived: &[usize] = match index {
+            0 => &[0],
+            1 => &[1],
+            2 => &[0, 1],
+            _ => {
+                return Err(IKosError {
+                    error: String::from("index error"),
+                });
+            }
+        };
+        for &branch in derived {
+            let mut in_data = vec![];
+            for _ in 0..proof.input_len {
+                in_data.push(get_next_random_from_context(&mut ctx[branch])?);
+            }
+            ctx[branch].ikos_view.in_data = in_data;
+        }
+        if ctx.iter().any(|c| c.ikos_view.in_data.len() != proof.input_len) {
+            return Err(IKosError {
+                error: String::from("verify input length error"),
+            });
+        }
+        // The first opened view is recomputed gate by gate; of the
+        // second only the gate outputs are sent.
+        ctx[0].ikos_view.out_data = vec![];
+
+        let ctx = SharedContexts::new(ctx);
+        // input
+        let mut ikos_input = vec![];
+        for i in 0..proof.input_len {
+            let shares = ctx.lock().iter().map(|c| c.ikos_view.in_data[i]).collect();
+            ikos_input.push(IKosVariable4V::new_share(shares, ctx.clone()));
+        }
+
+        // rut circuit
+        let ikos_out = proof.run_circuit(&ikos_input, &proof.input_pub)?;
+
+        let mut contexts = ctx.lock();
+        if contexts[1].out_view_ctr != contexts[1].ikos_view.out_data.len() {
+            return Err(IKosError {
+                error: String::from("verify view length error"),
+            });
+        }
+        for (branch, context) in contexts
+            .iter_mut()
+            .enumerate()
+            .take(self.num_of_public_branch)
+        {
+            for ikos in &ikos_out {
+                context.ikos_view.out_data.push(ikos.value[branch]);
+                context.out_view_ctr += 1;
+            }
+        }
+
+        // construct three views: the commitment of the unopened party
+        // comes from the proof, the other two are recomputed.
+        let sent = &proof.two_views[round * self.commit_length..(round + 1) * self.commit_length];
+        let commit0 = contexts[0].commit_ikos_context();
+        let commit1 = contexts[1].commit_ikos_context();
+        let ordered: [&[u8]; 3] = match index {
+            0 => [sent, &commit0, &commit1],
+            1 => [&commit1, sent, &commit0],
+            _ => [&commit0, &commit1, sent],
+        };
+        let three_views = ordered.concat();
+
+        // rebuild shares
+        let mut out_shares = vec![];
+        for (i, ikos) in ikos_out.iter().enumerate() {
+            let (v0, v1) = (ikos.value[0], ikos.value[1]);
+            let missing = proof.output[i] ^ v0 ^ v1;
+            out_shares.extend(&match index {
+                0 => [missing, v0, v1],
+                1 => [v1, missing, v0],
+                _ => [v0, v1, missing],
+            });
+        }
+        Ok(RoundCommitments { three_views, out_shares })
     }
 
-    fn choose_index_from_challenge(&self, commit: &[u8; 32]) -> Vec<usize> {
-        let mut res = vec![];
-        let mut val = 0;
-        for &com in commit.iter().take(4) {
-            val = val as usize * 16 + com as usize;
+    // Writes the output shares of one round, party-minor, into `vec_view`.
+    fn store_out_shares(&self, vec_view: &mut _3DVector, round: usize, out_shares: &[u32]) {
+        for (i, shares) in out_shares.chunks(self.num_of_branch).enumerate() {
+            for (party, &share) in shares.iter().enumerate() {
+                let index = vec_view.get_index(i, round, party);
+                vec_view.data[index] = share;
+            }
         }
+    }
 
-        for _ in 0..self.num_of_round {
-            res.push(val % self.num_of_branch);
-            val /= self.num_of_branch;
+    // The party left unopened in each round. The challenge is expanded as
+    // sha256(challenge || uint32(counter)) with a big-endian counter and
+    // read two bits at a time from the most significant end, rejecting
+    // values of `num_of_branch` or more, so every round gets a uniform
+    // index of its own however many rounds there are.
+    fn choose_index_from_challenge(&self, commit: &[u8; 32]) -> Vec<usize> {
+        let mut res = vec![];
+        let mut counter: u32 = 0;
+        while res.len() < self.num_of_round {
+            let mut sha = Sha256::new();
+            sha.input(commit);
+            sha.input(&counter.to_be_bytes());
+            let mut stream = [0; 32];
+            sha.result(&mut stream);
+            for &byte in stream.iter() {
+                for shift in [6, 4, 2, 0].iter() {
+                    let index = (byte >> shift) as usize & 3;
+                    if index < self.num_of_branch && res.len() < self.num_of_round {
+                        res.push(index);
+                    }
+                }
+            }
+            counter += 1;
         }
         res
     }
@@ -370,24 +490,32 @@ impl ZkBoo {
         res
     }
 
-    pub fn build_response(&self, views: &[IKosView], challenge: &[u8; 32]) -> Vec<IKosView> {
+    /// Opens the two views the challenge asks for, keeping only what the
+    /// verifier cannot recompute (ZKB++): input shares of parties 0 and 1
+    /// come from their tapes, the first opened view is replayed from the
+    /// second, and output shares follow from the gate outputs.
+    pub fn build_response(&self, proof: &Proof, challenge: &[u8; 32]) -> Vec<IKosView> {
         let index_vec = self.choose_index_from_challenge(challenge);
         let mut res = vec![];
         for (round, &index) in index_vec.iter().enumerate().take(self.num_of_round) {
-            match index {
-                0 => {
-                    res.push(views[round * self.num_of_branch + 1].clone());
-                    res.push(views[round * self.num_of_branch + 2].clone());
-                }
-                1 => {
-                    res.push(views[round * self.num_of_branch + 2].clone());
-                    res.push(views[round * self.num_of_branch].clone());
+            let parties = match index {
+                0 => [1, 2],
+                1 => [2, 0],
+                2 => [0, 1],
+                _ => continue,
+            };
+            for (opened, &party) in parties.iter().enumerate() {
+                let mut view = proof.views[round * self.num_of_branch + party].clone();
+                if party != 2 {
+                    view.in_data.clear();
                 }
-                2 => {
-                    res.push(views[round * self.num_of_branch].clone());
-                    res.push(views[round * self.num_of_branch + 1].clone());
+                if opened == 0 {
+                    view.out_data.clear();
+                } else {
+                    let gates = view.out_data.len() - proof.output.len();
+                    view.out_data.truncate(gates);
                 }
-                _ => {}
+                res.push(view);
             }
         }
         res
@@ -397,6 +525,17 @@ impl ZkBoo {
         let index_vec = self.choose_index_from_challenge(commit);
         self.discard_one_view(&proof.three_views, index_vec)
     }
+
+    /// Upper bound on the bytes a proof of `circuit` sends: per round the
+    /// unopened commitment and the two opened views, counting the third
+    /// party's input shares as if it were opened every round, then the
+    /// challenge, the input length, the public inputs and the output.
+    pub fn proof_size(&self, circuit: &Circuit) -> usize {
+        let stats = circuit.stats();
+        let seeds = self.num_of_public_branch * (RAND_TAPE_SEED_LEN + BLINDING_LEN);
+        let round = self.commit_length + seeds + 4 * (stats.inputs + stats.view_words());
+        self.num_of_round * round + 32 + 4 * (1 + stats.public_inputs + stats.outputs)
+    }
 }
 
 impl Proof {
@@ -417,8 +556,20 @@ impl Proof {
             output,
             three_views,
             views,
+            transcript: Transcript::default(),
         }
     }
+
+    /// Fiat-Shamir challenge over this proof and its transcript.
+    pub fn challenge(&self) -> [u8; 32] {
+        self.transcript.challenge(
+            self.input_len,
+            &self.input_pub,
+            &self.output,
+            &self.out_data,
+            &self.three_views,
+        )
+    }
 }
 
 impl ProvingProof {
@@ -433,8 +584,29 @@ impl ProvingProof {
             input_pub,
             output_len,
             circuit,
+            seeder: Box::new(OsSeeder),
+            transcript: Transcript::default(),
+            engine: Engine::PerRound,
         }
     }
+
+    pub fn with_engine(mut self, engine: Engine) -> Self {
+        self.engine = engine;
+        self
+    }
+
+    /// Replaces the default OS entropy with another source of tape seeds,
+    /// e.g. a `MasterSeeder` to replay a proof byte for byte.
+    pub fn with_seeder(mut self, seeder: Box<dyn TapeSeeder>) -> Self {
+        self.seeder = seeder;
+        self
+    }
+
+    /// Binds the proof to a circuit digest and an optional context.
+    pub fn with_transcript(mut self, transcript: Transcript) -> Self {
+        self.transcript = transcript;
+        self
+    }
 }
 
 impl VerifyingProof {
@@ -445,7 +617,7 @@ impl VerifyingProof {
         challenge: [u8; 32],
         two_views: Vec<u8>,
         response: Vec<IKosView>,
-        circuit: Circuit4V,
+        circuit: Box<dyn Circuit4VTrait>,
     ) -> Self {
         VerifyingProof {
             input_len,
@@ -455,14 +627,192 @@ impl VerifyingProof {
             two_views,
             response,
             circuit,
+            transcript: Transcript::default(),
         }
     }
 
+    /// The transcript the proof is expected to have been made under.
+    pub fn with_transcript(mut self, transcript: Transcript) -> Self {
+        self.transcript = transcript;
+        self
+    }
+
     fn run_circuit(
         &self,
         ikos_input: &[IKosVariable4V],
         input_pub: &[u32],
     ) -> IKosResult<Vec<IKosVariable4V>> {
-        (self.circuit)(ikos_input, input_pub)
+        self.circuit.circuit(ikos_input, input_pub)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::view_bytes;
+    use crate::zkboo::ikos::MasterSeeder;
+
+    struct Sum;
+
+    impl Circuit4PTrait for Sum {
+        fn circuit(&self, input: Vec<IKosVariable4P>, _input_pub: &[u32]) -> Vec<IKosVariable4P> {
+            vec![input[0].clone().add_op(&input[1])]
+        }
+    }
+
+    fn sum_4v(input: &[IKosVariable4V], _input_pub: &[u32]) -> IKosResult<Vec<IKosVariable4V>> {
+        Ok(vec![input[0].clone().add_op(&input[1])?])
+    }
+
+    fn prove_sum(zk_boo: &ZkBoo, master: [u8; 32]) -> Proof {
+        let proof = ProvingProof::new(vec![3, 4], vec![], 1, Box::new(Sum))
+            .with_seeder(Box::new(MasterSeeder::new(master)))
+            .with_transcript(sum_transcript());
+        zk_boo.prove(proof).unwrap()
+    }
+
+    fn sum_transcript() -> Transcript {
+        Transcript::new(Transcript::circuit_digest("sum")).with_context(b"test".to_vec())
+    }
+
+    fn verify_sum(zk_boo: &ZkBoo, proof: &mut Proof, input_pub: Vec<u32>, transcript: Transcript) -> IKosResult<bool> {
+        let challenge = proof.challenge();
+        let response = zk_boo.build_response(proof, &challenge);
+        let two_views = zk_boo.rebuild_proof(proof, &challenge);
+        let verifying = VerifyingProof::new(
+            proof.input_len,
+            input_pub,
+            proof.output.clone(),
+            challenge,
+            two_views,
+            response,
+            Box::new(sum_4v as Circuit4V),
+        )
+        .with_transcript(transcript);
+        zk_boo.verify(verifying)
+    }
+
+    #[test]
+    fn test_master_seed_replays_proof() {
+        let zk_boo = ZkBoo::new(2, 3, 2, 32);
+        let proof = prove_sum(&zk_boo, [7; 32]);
+        assert_eq!(proof, prove_sum(&zk_boo, [7; 32]));
+        assert_ne!(proof.three_views, prove_sum(&zk_boo, [8; 32]).three_views);
+        assert_eq!(proof.output, vec![7]);
+    }
+
+    #[test]
+    fn test_replayed_proof_verifies() {
+        let zk_boo = ZkBoo::new(2, 3, 2, 32);
+        let mut proof = prove_sum(&zk_boo, [1; 32]);
+        assert_eq!(verify_sum(&zk_boo, &mut proof, vec![], sum_transcript()), Ok(true));
+    }
+
+    #[test]
+    fn test_proof_rejected_under_another_statement() {
+        let zk_boo = ZkBoo::new(2, 3, 2, 32);
+        let mut proof = prove_sum(&zk_boo, [1; 32]);
+        let other_circuit = Transcript::new(Transcript::circuit_digest("product")).with_context(b"test".to_vec());
+        let other_context = sum_transcript().with_context(b"other".to_vec());
+        assert_eq!(verify_sum(&zk_boo, &mut proof, vec![], other_circuit), Ok(false));
+        assert_eq!(verify_sum(&zk_boo, &mut proof, vec![], other_context), Ok(false));
+        assert_eq!(verify_sum(&zk_boo, &mut proof, vec![9], sum_transcript()), Ok(false));
+    }
+
+    // Adds its inputs four times over, so gate outputs outweigh the seeds.
+    struct RepeatedSum;
+
+    impl Circuit4PTrait for RepeatedSum {
+        fn circuit(&self, input: Vec<IKosVariable4P>, _input_pub: &[u32]) -> Vec<IKosVariable4P> {
+            let all = input.iter().cycle().take(4 * input.len()).skip(1);
+            vec![all.fold(input[0].clone(), |acc, x| acc.add_op(x))]
+        }
+    }
+
+    #[test]
+    fn test_response_is_about_half_of_the_opened_views() {
+        let zk_boo = ZkBoo::new(2, 3, 2, 32);
+        let proving = ProvingProof::new((1..=8).collect(), vec![], 1, Box::new(RepeatedSum))
+            .with_seeder(Box::new(MasterSeeder::new([3; 32])));
+        let proof = zk_boo.prove(proving).unwrap();
+        assert_eq!(proof.output, vec![4 * 36]);
+        let challenge = proof.challenge();
+        let response = zk_boo.build_response(&proof, &challenge);
+
+        let full: usize = 2 * proof.views.iter().map(view_bytes).sum::<usize>() / 3;
+        let sent: usize = response.iter().map(view_bytes).sum();
+        assert!(5 * sent <= 3 * full, "{} of {} bytes", sent, full);
+    }
+
+    #[test]
+    fn test_tampered_gate_output_is_rejected() {
+        let zk_boo = ZkBoo::new(2, 3, 2, 32);
+        let mut proof = prove_sum(&zk_boo, [1; 32]);
+        let challenge = proof.challenge();
+        let mut response = zk_boo.build_response(&proof, &challenge);
+        response[1].out_data[0] ^= 1;
+        let two_views = zk_boo.rebuild_proof(&mut proof, &challenge);
+        let verifying = VerifyingProof::new(
+            proof.input_len,
+            proof.input_pub.clone(),
+            proof.output.clone(),
+            challenge,
+            two_views,
+            response,
+            Box::new(sum_4v as Circuit4V),
+        )
+        .with_transcript(sum_transcript());
+        assert_eq!(zk_boo.verify(verifying), Ok(false));
+    }
+
+    #[test]
+    fn test_multi_word_output_verifies() {
+        // Every output word is checked against the view at its own
+        // position, not all against the first one.
+        let mut circuit = Circuit::new();
+        let (a, b) = (circuit.input(), circuit.input());
+        let and = circuit.and(a, b);
+        let sum = circuit.add(a, b);
+        let xor = circuit.xor(a, b);
+        circuit.set_outputs(vec![and, sum, xor]);
+        let zk_boo = ZkBoo::new(4, 3, 2, 32);
+        let proving = ProvingProof::new(vec![12, 10], vec![], 3, Box::new(circuit.clone()))
+            .with_seeder(Box::new(MasterSeeder::new([9; 32])));
+        let mut proof = zk_boo.prove(proving).unwrap();
+        assert_eq!(proof.output, vec![8, 22, 6]);
+        let challenge = proof.challenge();
+        let response = zk_boo.build_response(&proof, &challenge);
+        let two_views = zk_boo.rebuild_proof(&mut proof, &challenge);
+        let verifying = |output: Vec<u32>| {
+            VerifyingProof::new(proof.input_len, vec![], output, challenge, two_views.clone(), response.clone(), Box::new(circuit.clone()))
+        };
+        assert_eq!(zk_boo.verify(verifying(vec![8, 22, 6])), Ok(true));
+        assert_eq!(zk_boo.verify(verifying(vec![8, 22, 7])), Ok(false));
+    }
+
+    #[test]
+    fn test_every_round_gets_its_own_index() {
+        let zk_boo = ZkBoo::new(137, 3, 2, 32);
+        let mut counts = [0; 3];
+        for seed in 0..20u8 {
+            let indices = zk_boo.choose_index_from_challenge(&[seed; 32]);
+            assert_eq!(indices.len(), 137);
+            // Only 4 challenge bytes used to feed the indices, which ran
+            // out after about 13 rounds and left the rest at 0.
+            assert!(indices[13..].iter().any(|&index| index != 0));
+            for index in indices {
+                counts[index] += 1;
+            }
+        }
+        // 2740 draws, about 913 each.
+        assert!(counts.iter().all(|&count| count > 800 && count < 1030), "{:?}", counts);
+    }
+
+    #[test]
+    fn test_many_rounds_verify() {
+        let zk_boo = ZkBoo::new(137, 3, 2, 32);
+        let mut proof = prove_sum(&zk_boo, [5; 32]);
+        assert_eq!(proof.views.len(), 137 * 3);
+        assert_eq!(verify_sum(&zk_boo, &mut proof, vec![], sum_transcript()), Ok(true));
     }
 }